@@ -1,31 +1,121 @@
-use crate::state::server::{SqLiteBlockStore, SqLiteMessagePool};
+use crate::config::network::MAX_BLOCK_RANGE;
+use crate::state::server::{
+    SqLiteBlockStore, SqLiteEvidenceStore, SqLiteMessageIndex, SqLiteMessagePool,
+};
 use crate::{
-    consensus::logic::{current_round, evaluate_commitment, get_committing_validator},
-    crypto::ecdsa::deserialize_vk,
-    handlers::handle_block_proposal,
+    consensus::logic::{
+        current_round, evaluate_commitment, get_committing_validator, verify_commitment,
+    },
+    handlers::{handle_block_proposal, handle_timeout_vote},
     state::server::{BlockStore, InMemoryConsensus, MessagePool},
-    types::{Block, ConsensusCommitment, Message},
+    types::{
+        Block, ConsensusCommitment, FinalizedBlock, FinalizedHeader, InclusionStatus, Message,
+        MessageLookup, MessageReceipt, MessageStatus, TimeoutVote,
+    },
     ServerState,
 };
-use axum::{extract::Path, Extension, Json};
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
 use ciphercurve_trie::store::types::Node;
 use colored::Colorize;
 use k256::ecdsa::signature::Verifier;
 use k256::ecdsa::Signature;
-use l2_sequencer::config::consensus::ROUND_DURATION;
+use serde::Deserialize;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 pub async fn schedule(
     Extension(_): Extension<Arc<Mutex<ServerState>>>,
-    Extension(_): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
     Extension(shared_pool_state): Extension<Arc<Mutex<MessagePool>>>,
-    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Extension(shared_consensus_state): Extension<Arc<Mutex<InMemoryConsensus>>>,
     Json(message): Json<Message>,
 ) -> String {
+    let chain_id = shared_consensus_state.lock().await.genesis.chain_id.clone();
+    if let Err(e) = message.verify_envelope(&chain_id) {
+        return format!("[Error] {}", e);
+    }
+    // every lock is released before the next one is taken, the consensus loop holds several
+    let finalized_nonce = match &message.envelope {
+        Some(envelope) => shared_block_state
+            .lock()
+            .await
+            .get_finalized_nonce(&envelope.sender),
+        None => None,
+    };
     let mut shared_pool_lock = shared_pool_state.lock().await;
-    let success_response = format!("[Ok] Message is being sequenced: {:?}", &message).to_string();
+    if let Some(envelope) = &message.envelope {
+        // the pool only knows the messages scheduled at this node, the Blocks know all of them
+        if finalized_nonce.is_some_and(|finalized_nonce| envelope.nonce <= finalized_nonce)
+            || !shared_pool_lock.check_and_record_nonce(&envelope.sender, envelope.nonce)
+        {
+            return format!(
+                "[Error] Nonce {} does not exceed the last nonce of the sender",
+                envelope.nonce
+            );
+        }
+    }
+    let receipt = MessageReceipt {
+        id: hex::encode(message.id()),
+        payload_hash: hex::encode(message.payload_hash()),
+    };
     shared_pool_lock.insert_message(message);
-    success_response
+    serde_json::to_string(&receipt).unwrap()
+}
+pub async fn get_message_status(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(shared_pool_state): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(id): Path<String>,
+) -> String {
+    let id = match hex::decode(&id) {
+        Ok(id) => id,
+        Err(_) => return "[Error] Message id must be hex encoded".to_string(),
+    };
+    // the pool is released before the block store is locked, the consensus loop holds both
+    let (receipt, pending) = {
+        let pool_state_lock = shared_pool_state.lock().await;
+        // only messages scheduled at this node have a receipt
+        match pool_state_lock.get_receipt(&id) {
+            Some(receipt) => (receipt, pool_state_lock.contains_message(&id)),
+            None => return "[Warning] Message id is unknown to this node".to_string(),
+        }
+    };
+    let block_state_lock = shared_block_state.lock().await;
+    let included = block_state_lock
+        .get_message_locations(&receipt.payload_hash)
+        .into_iter()
+        .find(|location| {
+            block_state_lock
+                .get_message_by_sequence(location.sequence)
+                .is_some_and(|sequenced_message| sequenced_message.message.id() == id)
+        });
+    let status = match (included, receipt.proposed_height) {
+        (Some(location), _) => MessageStatus::Included {
+            reason: format!("Finalized in the Block at height {}", location.height),
+            location,
+        },
+        (None, _) if pending => MessageStatus::Pending {
+            reason: "Waiting in the message pool".to_string(),
+        },
+        (None, Some(height)) if height < block_state_lock.current_block_height() => {
+            MessageStatus::Dropped {
+                reason: format!(
+                    "The Block finalized at height {} does not contain the message",
+                    height
+                ),
+            }
+        }
+        (None, Some(height)) => MessageStatus::Pending {
+            reason: format!("Proposed at height {}, waiting for finalization", height),
+        },
+        (None, None) => MessageStatus::Dropped {
+            reason: "Removed from the message pool before it was proposed".to_string(),
+        },
+    };
+    serde_json::to_string(&status).unwrap()
 }
 pub async fn commit(
     Extension(_): Extension<Arc<Mutex<ServerState>>>,
@@ -43,27 +133,84 @@ pub async fn commit(
     }
     let block_state_lock = maybe_block_lock.expect("Failed to unwrap block lock");
     let mut consensus_state_lock = maybe_consensus_lock.expect("Failed to unwrap consensus lock");
-    let success_response = format!("[Ok] Commitment was accepted: {:?}", &commitment).to_string();
+    let success_response = format!("[Ok] Commitment was accepted: {:?}", commitment).to_string();
     let last_block_unix_timestamp = block_state_lock
         .get_block_by_height(block_state_lock.current_block_height() - 1)
+        .header
         .timestamp;
-    if !consensus_state_lock.round_winner.is_some() {
-        // no round winner found, commitment might be valid
-        let validator = get_committing_validator(
-            last_block_unix_timestamp,
-            consensus_state_lock.validators.clone(),
-        );
-        // todo: check if commitment signature is valid for validator
-        if deserialize_vk(&commitment.validator) == validator {
-            let winner = evaluate_commitment(commitment, consensus_state_lock.validators.clone());
-            println!("[Info] Winner chosen!");
-            consensus_state_lock.round_winner = Some(winner);
+    // every commitment is verified, also once the round winner is known, so that a forged
+    // commitment is never answered as accepted
+    let validator = get_committing_validator(
+        last_block_unix_timestamp,
+        consensus_state_lock.genesis.round_duration,
+        &consensus_state_lock.validators,
+    );
+    let next_height = block_state_lock.current_block_height();
+    let round = current_round(
+        last_block_unix_timestamp,
+        consensus_state_lock.genesis.round_duration,
+    );
+    let winner = match verify_commitment(
+        &commitment,
+        &validator,
+        &consensus_state_lock.genesis.chain_id,
+        next_height,
+        round,
+    )
+    .and_then(|_| {
+        evaluate_commitment(
+            &commitment,
+            consensus_state_lock.leader_election.as_ref(),
+            &consensus_state_lock.validators,
+        )
+    }) {
+        Ok(winner) => winner,
+        Err(e) => {
+            println!(
+                "{}",
+                format_args!("{} Commitment rejected: {}", "[Warning]".yellow(), e)
+            );
+            return format!("[Error] Commitment rejected: {}", e);
         }
+    };
+    if consensus_state_lock.round_winner.is_none() {
+        println!("[Info] Winner chosen!");
+        consensus_state_lock.round_winner = Some(winner);
     } else {
         println!("[Warning] Winner was already chosen, or not re-set!");
     }
     success_response
 }
+pub async fn timeout(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(shared_consensus_state): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Json(vote): Json<TimeoutVote>,
+) -> String {
+    let block_state_lock = shared_block_state.lock().await;
+    let mut consensus_state_lock = shared_consensus_state.lock().await;
+    let next_height = block_state_lock.current_block_height();
+    let last_block_unix_timestamp = block_state_lock
+        .get_block_by_height(next_height - 1)
+        .header
+        .timestamp;
+    drop(block_state_lock);
+    let round = current_round(
+        last_block_unix_timestamp,
+        consensus_state_lock.genesis.round_duration,
+    );
+    match handle_timeout_vote(&mut consensus_state_lock, vote, next_height, round) {
+        Ok(_) => "[Ok] Timeout vote was accepted".to_string(),
+        Err(e) => {
+            println!(
+                "{}",
+                format_args!("{} Timeout vote rejected: {}", "[Warning]".yellow(), e)
+            );
+            format!("[Error] Timeout vote rejected: {}", e)
+        }
+    }
+}
 pub async fn propose(
     Extension(shared_state): Extension<Arc<Mutex<ServerState>>>,
     Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
@@ -76,13 +223,19 @@ pub async fn propose(
     let consensus_state_lock = shared_consensus_state.lock().await;
     let last_block_unix_timestamp = block_state_lock
         .get_block_by_height(block_state_lock.current_block_height() - 1)
+        .header
         .timestamp;
-    let error_response = format!("Block was rejected: {:?}", &proposal).to_string();
-    let round = current_round(last_block_unix_timestamp);
-    if proposal.timestamp < last_block_unix_timestamp + ((round - 1) * (ROUND_DURATION)) {
+    let error_response = format!("Block was rejected: {:?}", proposal).to_string();
+    let round_duration = consensus_state_lock.genesis.round_duration;
+    let round = current_round(last_block_unix_timestamp, round_duration);
+    // the proposer chooses the timestamp, it must fall into the current round
+    let round_start = last_block_unix_timestamp + ((round - 1) * (round_duration));
+    if proposal.header.timestamp < round_start
+        || proposal.header.timestamp >= round_start + round_duration
+    {
         println!(
             "[Warning] Invalid Proposal Timestamp: {}",
-            proposal.timestamp
+            proposal.header.timestamp
         );
         return error_response;
     };
@@ -92,10 +245,10 @@ pub async fn propose(
         .expect("Block has not been signed!");
     if let Some(round_winner) = consensus_state_lock.round_winner {
         drop(consensus_state_lock);
-        if !block_state_lock.block_exists(proposal.height) {
+        if !block_state_lock.block_exists(proposal.header.height) {
             drop(block_state_lock);
             let signature_deserialized = Signature::from_slice(&block_signature).unwrap();
-            match round_winner.verify(&proposal.to_bytes(), &signature_deserialized) {
+            match round_winner.verify(&proposal.hash(), &signature_deserialized) {
                 Ok(_) => {
                     let res = handle_block_proposal(
                         Arc::clone(&shared_state),
@@ -105,9 +258,8 @@ pub async fn propose(
                         error_response,
                     )
                     .await;
-                    match res {
-                        Some(e) => return e,
-                        None => {}
+                    if let Some(e) = res {
+                        return e;
                     }
                 }
                 Err(_) => {
@@ -141,13 +293,64 @@ pub async fn merkle_proof(
     let mut state_lock = shared_state.lock().await;
     let trie_root = state_lock.merkle_trie_root.clone();
     // todo: make merkle proof fn accept an immutable trie state instance
-    let merkle_proof = ciphercurve_trie::merkle::merkle_proof(
+    match ciphercurve_trie::merkle::merkle_proof(
         &mut state_lock.merkle_trie_state,
         key,
         Node::Root(trie_root),
-    )
-    .expect("Failed to get merkle proof!");
-    serde_json::to_string(&merkle_proof).unwrap()
+    ) {
+        Some(merkle_proof) => serde_json::to_string(&merkle_proof).unwrap(),
+        None => "[Warning] Key is not in the trie, request an exclusion proof instead".to_string(),
+    }
+}
+// proves that a payload hash is absent from, or present in, the message index of a Block
+pub async fn exclusion_proof(
+    Extension(shared_state): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(height): Path<u32>,
+    Json(payload_hash): Json<Vec<u8>>,
+) -> String {
+    let block_state_lock = shared_block_state.lock().await;
+    if !block_state_lock.block_exists(height) {
+        return "[Warning] Requested Block that does not exist".to_string();
+    }
+    let message_index_root = block_state_lock
+        .get_block_by_height(height)
+        .header
+        .message_index_root;
+    drop(block_state_lock);
+    let state_lock = shared_state.lock().await;
+    match state_lock
+        .message_index
+        .prove(&message_index_root, &payload_hash)
+    {
+        Ok(proof) => serde_json::to_string(&proof).unwrap(),
+        Err(e) => format!("[Error] Failed to prove payload hash: {}", e),
+    }
+}
+// proves against the trie root of a past Block instead of the latest one
+pub async fn merkle_proof_at_height(
+    Extension(shared_state): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(height): Path<u32>,
+    Json(key): Json<Vec<u8>>,
+) -> String {
+    let trie_root = match shared_block_state.lock().await.get_state_root(height) {
+        Some(trie_root) => trie_root,
+        None => return "[Warning] Requested state root that does not exist".to_string(),
+    };
+    let mut state_lock = shared_state.lock().await;
+    match ciphercurve_trie::merkle::merkle_proof(
+        &mut state_lock.merkle_trie_state,
+        key,
+        Node::Root(trie_root),
+    ) {
+        Some(merkle_proof) => serde_json::to_string(&merkle_proof).unwrap(),
+        None => format!("[Warning] Key is not in the trie at height: {}", height),
+    }
 }
 pub async fn get_pool(
     Extension(_): Extension<Arc<Mutex<ServerState>>>,
@@ -169,6 +372,30 @@ pub async fn get_commitments(
     let consensus_state_lock = consensus_state.lock().await;
     format!("{:?}", consensus_state_lock.commitments)
 }
+pub async fn get_timeout_certificates(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(_): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(consensus_state): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(height): Path<u32>,
+) -> String {
+    let consensus_state_lock = consensus_state.lock().await;
+    serde_json::to_string(
+        &consensus_state_lock
+            .evidence
+            .get_timeout_certificates(height),
+    )
+    .unwrap()
+}
+pub async fn get_equivocations(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(_): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(consensus_state): Extension<Arc<Mutex<InMemoryConsensus>>>,
+) -> String {
+    let consensus_state_lock = consensus_state.lock().await;
+    serde_json::to_string(&consensus_state_lock.evidence.get_equivocations()).unwrap()
+}
 pub async fn get_block(
     Extension(_): Extension<Arc<Mutex<ServerState>>>,
     Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
@@ -185,12 +412,50 @@ pub async fn get_block(
     if previous_block_height < height + 1 {
         "[Warning] Requested Block that does not exist".to_string()
     } else {
-        match serde_json::to_string(&block_state_lock.get_block_by_height(height)) {
+        let finalized_block = FinalizedBlock {
+            block: block_state_lock.get_block_by_height(height),
+            quorum_certificate: block_state_lock.get_quorum_certificate(height),
+        };
+        match serde_json::to_string(&finalized_block) {
             Ok(block_json) => block_json,
             Err(e) => e.to_string(),
         }
     }
 }
+#[derive(Deserialize)]
+pub struct BlockRange {
+    from: u32,
+    to: u32,
+}
+// serves at most MAX_BLOCK_RANGE Blocks with their quorum certificates, both bounds are inclusive
+pub async fn get_blocks(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Query(range): Query<BlockRange>,
+) -> String {
+    let block_state_lock = shared_block_state.lock().await;
+    let to = range.to.min(range.from.saturating_add(MAX_BLOCK_RANGE - 1));
+    serde_json::to_string(&block_state_lock.get_finalized_blocks(range.from, to)).unwrap()
+}
+// the headers of a range of Blocks, for light clients that don't need the messages
+pub async fn get_headers(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Query(range): Query<BlockRange>,
+) -> String {
+    let block_state_lock = shared_block_state.lock().await;
+    let to = range.to.min(range.from.saturating_add(MAX_BLOCK_RANGE - 1));
+    let finalized_headers: Vec<FinalizedHeader> = block_state_lock
+        .get_finalized_blocks(range.from, to)
+        .iter()
+        .map(FinalizedBlock::finalized_header)
+        .collect();
+    serde_json::to_string(&finalized_headers).unwrap()
+}
 pub async fn get_state_root_hash(
     Extension(shared_state): Extension<Arc<Mutex<ServerState>>>,
     Extension(_): Extension<Arc<Mutex<BlockStore>>>,
@@ -203,6 +468,84 @@ pub async fn get_state_root_hash(
         Err(e) => e.to_string(),
     }
 }
+pub async fn get_message_by_sequence(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(sequence): Path<u64>,
+) -> String {
+    let block_state_lock = shared_block_state.lock().await;
+    match block_state_lock.get_message_by_sequence(sequence) {
+        Some(sequenced_message) => serde_json::to_string(&sequenced_message).unwrap(),
+        None => "[Warning] Requested sequence number that does not exist".to_string(),
+    }
+}
+// the locations of a hex encoded payload hash, empty if it was never sequenced
+pub async fn get_message_locations(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(_): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(payload_hash): Path<String>,
+) -> String {
+    let payload_hash = match hex::decode(&payload_hash) {
+        Ok(payload_hash) => payload_hash,
+        Err(_) => return "[Error] Payload hash must be hex encoded".to_string(),
+    };
+    let block_state_lock = shared_block_state.lock().await;
+    serde_json::to_string(&block_state_lock.get_message_locations(&payload_hash)).unwrap()
+}
+pub async fn get_message(
+    Extension(_): Extension<Arc<Mutex<ServerState>>>,
+    Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
+    Extension(shared_pool_state): Extension<Arc<Mutex<MessagePool>>>,
+    Extension(_): Extension<Arc<Mutex<InMemoryConsensus>>>,
+    Path(payload_hash): Path<String>,
+) -> String {
+    let payload_hash = match hex::decode(&payload_hash) {
+        Ok(payload_hash) => payload_hash,
+        Err(_) => return "[Error] Payload hash must be hex encoded".to_string(),
+    };
+    // the block store is released before the pool is locked, the consensus loop holds both
+    let included = {
+        let block_state_lock = shared_block_state.lock().await;
+        block_state_lock
+            .get_message_locations(&payload_hash)
+            .into_iter()
+            .next()
+            .map(|location| {
+                let message = block_state_lock
+                    .get_message_by_sequence(location.sequence)
+                    .map(|sequenced_message| sequenced_message.message);
+                (location, message)
+            })
+    };
+    let lookup = match included {
+        Some((location, message)) => MessageLookup {
+            status: InclusionStatus::Included,
+            message,
+            location: Some(location),
+        },
+        None => {
+            let pending_message = shared_pool_state
+                .lock()
+                .await
+                .get_all_messages()
+                .into_iter()
+                .find(|message| message.payload_hash() == payload_hash);
+            MessageLookup {
+                status: match pending_message {
+                    Some(_) => InclusionStatus::Pending,
+                    None => InclusionStatus::Unknown,
+                },
+                message: pending_message,
+                location: None,
+            }
+        }
+    };
+    serde_json::to_string(&lookup).unwrap()
+}
 pub async fn get_height(
     Extension(_): Extension<Arc<Mutex<ServerState>>>,
     Extension(shared_block_state): Extension<Arc<Mutex<BlockStore>>>,
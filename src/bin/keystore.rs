@@ -0,0 +1,49 @@
+use l2_sequencer::crypto::keystore::{generate_keystore, Keystore};
+use std::{env, io, process::exit};
+
+// usage:
+//   keystore generate <path>     creates a new validator key, encrypted with the passphrase
+//   keystore public-key <path>   prints the public key for the genesis validator set
+// the passphrase is read from KEYSTORE_PASSPHRASE or prompted on stdin
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: keystore <generate|public-key> <path>");
+        exit(1);
+    }
+    let path = &args[2];
+    match args[1].as_str() {
+        "generate" => {
+            let passphrase = read_passphrase();
+            let keypair = generate_keystore(path, &passphrase).unwrap_or_else(|e| {
+                eprintln!("[Error] Failed to generate keystore: {}", e);
+                exit(1)
+            });
+            println!("[Ok] Keystore written to: {}", path);
+            println!("Public key: {:?}", keypair.serialize_vk());
+        }
+        "public-key" => {
+            let keystore = Keystore::load(path).unwrap_or_else(|e| {
+                eprintln!("[Error] Failed to read keystore: {}", e);
+                exit(1)
+            });
+            println!("{:?}", keystore.public_key);
+        }
+        command => {
+            eprintln!("[Error] Unknown command: {}", command);
+            exit(1);
+        }
+    }
+}
+
+fn read_passphrase() -> String {
+    if let Ok(passphrase) = env::var("KEYSTORE_PASSPHRASE") {
+        return passphrase;
+    }
+    println!("Enter keystore passphrase:");
+    let mut passphrase = String::new();
+    io::stdin()
+        .read_line(&mut passphrase)
+        .expect("[Error] Failed to read passphrase");
+    passphrase.trim_end_matches(['\r', '\n']).to_string()
+}
@@ -0,0 +1,110 @@
+use axum::{
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use colored::Colorize;
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use l2_sequencer::{
+    config::consensus::local_signing_key,
+    crypto::{
+        ecvrf,
+        signer::{SignRequest, SignResponse, VrfRequest, VrfResponse},
+    },
+    types::GenericPublicKey,
+};
+use std::{env, net::SocketAddr, sync::Arc};
+
+// stand-in for a remote signer: holds the validator key loaded from the keystore
+// and signs on behalf of a node that sets REMOTE_SIGNER_URL=http://<SIGNER_HOST_WITH_PORT>
+// and REMOTE_SIGNER_TOKEN=<SIGNER_TOKEN>. It only listens on loopback, only answers requests
+// that carry the token and only signs consensus payloads, never arbitrary bytes
+#[derive(Clone)]
+struct SignerState {
+    signing_key: Arc<SigningKey>,
+    token: Arc<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let state = SignerState {
+        signing_key: Arc::new(local_signing_key()),
+        token: Arc::new(env::var("SIGNER_TOKEN").expect("[Error] SIGNER_TOKEN must be set")),
+    };
+    let host_with_port = env::var("SIGNER_HOST_WITH_PORT").unwrap_or("127.0.0.1:9000".to_string());
+    let address: SocketAddr = host_with_port
+        .parse()
+        .expect("[Error] SIGNER_HOST_WITH_PORT must be an ip:port");
+    assert!(
+        address.ip().is_loopback(),
+        "[Error] The Signer only listens on a loopback address"
+    );
+    println!(
+        "{}{}",
+        "Starting Signer: ".green().italic(),
+        host_with_port.yellow().bold()
+    );
+    let api = Router::new()
+        .route("/public_key", get(public_key))
+        .route("/sign", post(sign))
+        .route("/vrf_prove", post(vrf_prove))
+        .layer(Extension(state));
+    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
+    axum::serve(listener, api).await.unwrap();
+}
+
+// compares in constant time so the token can't be guessed byte by byte
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(provided) = headers
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn public_key(
+    Extension(state): Extension<SignerState>,
+    headers: HeaderMap,
+) -> Result<Json<GenericPublicKey>, StatusCode> {
+    if !authorized(&headers, &state.token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(Json(
+        state.signing_key.verifying_key().to_sec1_bytes().to_vec(),
+    ))
+}
+
+async fn sign(
+    Extension(state): Extension<SignerState>,
+    headers: HeaderMap,
+    Json(request): Json<SignRequest>,
+) -> Result<Json<SignResponse>, StatusCode> {
+    if !authorized(&headers, &state.token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let signature: Signature = state.signing_key.sign(&request.signing_bytes());
+    Ok(Json(SignResponse {
+        signature: signature.to_bytes().to_vec(),
+    }))
+}
+
+async fn vrf_prove(
+    Extension(state): Extension<SignerState>,
+    headers: HeaderMap,
+    Json(request): Json<VrfRequest>,
+) -> Result<Json<VrfResponse>, StatusCode> {
+    if !authorized(&headers, &state.token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(Json(VrfResponse {
+        proof: ecvrf::prove(&state.signing_key, &request.round.seed()),
+    }))
+}
@@ -1,66 +1,98 @@
-use crate::crypto::ecdsa::{deserialize_sk, deserialize_vk};
-use k256::ecdsa::{SigningKey, VerifyingKey};
+#[cfg(feature = "local-net")]
+use crate::crypto::ecdsa::deserialize_sk;
+use crate::crypto::{
+    keystore::load_keystore,
+    signer::{LocalSigner, RemoteSigner, Signer},
+};
+use k256::ecdsa::SigningKey;
+use std::{env, fs, sync::Arc};
 
-// time before a new block is created, from a block's timestamp onwards
-// specified in seconds
-pub const CLEARING_PHASE: u32 = 60;
-pub const ROUND_DURATION: u32 = 120; // total duration CLEARING PHASE + ROUND_DURATION!
-pub const CONSENSUS_THRESHOLD: u32 = 3;
-pub const GOSSIP_PROPOSAL_RETRY_LIMIT_PER_PEER: u32 = 10;
+// round duration, clearing phase and consensus threshold are defined in the genesis file
 
+#[cfg(feature = "local-net")]
 pub const TEST_V1_SK: &[u8] = &[
     197, 131, 252, 199, 111, 171, 195, 194, 6, 111, 156, 165, 24, 173, 168, 49, 220, 204, 234, 73,
     99, 125, 215, 189, 192, 254, 218, 47, 55, 40, 214, 117,
 ];
-pub const TEST_V1_VK: &[u8] = &[
-    2, 145, 6, 132, 63, 12, 220, 31, 107, 229, 80, 59, 38, 153, 140, 235, 182, 43, 206, 83, 189, 7,
-    223, 91, 52, 126, 122, 10, 55, 62, 238, 7, 219,
-];
+#[cfg(feature = "local-net")]
 pub const TEST_V2_SK: &[u8] = &[
     31, 133, 86, 165, 209, 28, 9, 200, 44, 211, 32, 243, 68, 35, 181, 101, 112, 158, 112, 89, 132,
     37, 223, 101, 46, 64, 204, 23, 247, 13, 207, 129,
 ];
-pub const TEST_V2_VK: &[u8] = &[
-    2, 117, 224, 184, 15, 207, 177, 48, 93, 85, 52, 230, 31, 123, 67, 27, 47, 58, 245, 197, 215,
-    189, 128, 192, 14, 203, 86, 139, 85, 234, 68, 42, 148,
-];
+#[cfg(feature = "local-net")]
 pub const TEST_V3_SK: &[u8] = &[
     78, 194, 130, 103, 15, 45, 121, 75, 122, 24, 22, 185, 195, 164, 25, 189, 183, 163, 231, 221,
     37, 26, 101, 13, 69, 29, 77, 206, 217, 20, 77, 21,
 ];
-pub const TEST_V3_VK: &[u8] = &[
-    2, 217, 50, 215, 153, 42, 245, 61, 71, 110, 21, 55, 183, 0, 19, 78, 156, 8, 121, 68, 11, 122,
-    51, 85, 220, 37, 239, 242, 201, 160, 77, 125, 239,
-];
+#[cfg(feature = "local-net")]
 pub const TEST_V4_SK: &[u8] = &[
     9, 104, 30, 6, 93, 140, 7, 212, 211, 42, 112, 63, 19, 114, 222, 151, 216, 159, 111, 1, 72, 50,
     218, 221, 135, 152, 242, 175, 208, 8, 82, 16,
 ];
-pub const TEST_V4_VK: &[u8] = &[
-    2, 224, 125, 18, 54, 62, 252, 187, 84, 81, 249, 80, 161, 32, 46, 212, 182, 246, 46, 4, 182,
-    124, 62, 192, 144, 236, 185, 255, 179, 94, 0, 46, 190,
-];
+#[cfg(feature = "local-net")]
 pub fn v1_sk_deserialized() -> SigningKey {
     deserialize_sk(TEST_V1_SK)
 }
-pub fn v1_vk_deserialized() -> VerifyingKey {
-    deserialize_vk(TEST_V1_VK)
-}
+#[cfg(feature = "local-net")]
 pub fn v2_sk_deserialized() -> SigningKey {
     deserialize_sk(TEST_V2_SK)
 }
-pub fn v2_vk_deserialized() -> VerifyingKey {
-    deserialize_vk(TEST_V2_VK)
-}
+#[cfg(feature = "local-net")]
 pub fn v3_sk_deserialized() -> SigningKey {
     deserialize_sk(TEST_V3_SK)
 }
-pub fn v3_vk_deserialized() -> VerifyingKey {
-    deserialize_vk(TEST_V3_VK)
-}
+#[cfg(feature = "local-net")]
 pub fn v4_sk_deserialized() -> SigningKey {
     deserialize_sk(TEST_V4_SK)
 }
-pub fn v4_vk_deserialized() -> VerifyingKey {
-    deserialize_vk(TEST_V4_VK)
+// uses the remote signer at REMOTE_SIGNER_URL with the token in REMOTE_SIGNER_TOKEN if
+// configured, otherwise signs in-process
+pub async fn local_signer() -> Arc<dyn Signer> {
+    match env::var("REMOTE_SIGNER_URL") {
+        Ok(url) => Arc::new(
+            RemoteSigner::connect(
+                &url,
+                &env::var("REMOTE_SIGNER_TOKEN")
+                    .expect("[Error] REMOTE_SIGNER_TOKEN must be set to use a remote signer"),
+            )
+            .await
+            .unwrap_or_else(|e| panic!("[Error] Failed to connect to signer {}: {}", url, e)),
+        ),
+        Err(_) => Arc::new(LocalSigner::new(local_signing_key())),
+    }
+}
+// loads the validator signing key from the encrypted keystore at PATH_TO_KEYSTORE,
+// the passphrase is read from KEYSTORE_PASSPHRASE_FILE or KEYSTORE_PASSPHRASE
+pub fn local_signing_key() -> SigningKey {
+    let keystore_path = match env::var("PATH_TO_KEYSTORE") {
+        Ok(path) => path,
+        #[cfg(feature = "local-net")]
+        Err(_) => return local_validator_test_sk(),
+        #[cfg(not(feature = "local-net"))]
+        Err(_) => panic!("[Error] PATH_TO_KEYSTORE must be set"),
+    };
+    let passphrase = match env::var("KEYSTORE_PASSPHRASE_FILE") {
+        Ok(passphrase_path) => fs::read_to_string(passphrase_path)
+            .expect("[Error] Failed to read keystore passphrase file")
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        Err(_) => env::var("KEYSTORE_PASSPHRASE")
+            .expect("[Error] KEYSTORE_PASSPHRASE or KEYSTORE_PASSPHRASE_FILE must be set"),
+    };
+    load_keystore(&keystore_path, &passphrase)
+        .unwrap_or_else(|e| panic!("[Error] Failed to load keystore {}: {}", keystore_path, e))
+        .sk
+}
+// selects one of the test keys by the LOCAL_VALIDATOR index
+#[cfg(feature = "local-net")]
+pub fn local_validator_test_sk() -> SigningKey {
+    match env::var("LOCAL_VALIDATOR")
+        .unwrap_or(0.to_string())
+        .as_str()
+    {
+        "0" => v1_sk_deserialized(),
+        "1" => v2_sk_deserialized(),
+        "2" => v3_sk_deserialized(),
+        _ => v4_sk_deserialized(),
+    }
 }
@@ -0,0 +1,91 @@
+use crate::consensus::{
+    election::ElectionBackend,
+    quorum::QuorumRule,
+    validators::{Validator, ValidatorSet},
+};
+use crate::crypto::ecdsa::deserialize_vk;
+use crate::types::{GenericPublicKey, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GenesisValidator {
+    pub public_key: GenericPublicKey,
+    pub stake: u64,
+}
+
+// shared by all nodes of a network, every node must load the same document
+// so that they derive the same genesis Block and validator set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Genesis {
+    pub chain_id: String,
+    pub genesis_timestamp: Timestamp,
+    pub validators: Vec<GenesisValidator>,
+    pub quorum: QuorumRule,
+    pub leader_election: ElectionBackend,
+    // specified in seconds, the clearing phase is the start of each round
+    pub round_duration: u32,
+    pub clearing_phase: u32,
+    // seconds without a finalized block after which validators vote to change the view
+    pub view_timeout: u32,
+}
+impl Genesis {
+    pub fn load(path: &str) -> Genesis {
+        let genesis_json = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("[Error] Failed to read genesis file {}: {}", path, e));
+        let genesis: Genesis = serde_json::from_str(&genesis_json)
+            .unwrap_or_else(|e| panic!("[Error] Invalid genesis file {}: {}", path, e));
+        genesis.validate();
+        genesis
+    }
+    fn validate(&self) {
+        assert!(
+            !self.validators.is_empty(),
+            "[Error] Genesis contains no validators"
+        );
+        assert!(
+            self.validators.iter().all(|validator| validator.stake > 0),
+            "[Error] Genesis validators must have a stake"
+        );
+        assert!(
+            self.validators
+                .iter()
+                .try_fold(0u64, |total, validator| total.checked_add(validator.stake))
+                .is_some(),
+            "[Error] Genesis total stake overflows"
+        );
+        assert!(
+            self.quorum.is_valid(),
+            "[Error] Invalid genesis quorum rule"
+        );
+        assert!(
+            self.clearing_phase < self.round_duration,
+            "[Error] Genesis clearing phase must be shorter than the round duration"
+        );
+        assert!(
+            self.view_timeout > 0,
+            "[Error] Genesis view timeout must not be zero"
+        );
+    }
+    pub fn validator_set(&self) -> ValidatorSet {
+        ValidatorSet {
+            validators: self
+                .validators
+                .iter()
+                .map(|validator| Validator {
+                    public_key: deserialize_vk(&validator.public_key),
+                    stake: validator.stake,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[test]
+fn test_load_genesis() {
+    let genesis = Genesis::load("genesis.json");
+    assert_eq!(
+        genesis.validator_set().validators.len(),
+        genesis.validators.len()
+    );
+}
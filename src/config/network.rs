@@ -7,3 +7,7 @@ lazy_static! {
         "rust-node-4:8083"
     ];
 }
+// the most Blocks served by a single `/get/blocks` request
+pub const MAX_BLOCK_RANGE: u32 = 100;
+// seconds until a peer that served invalid Blocks is synchronized from again
+pub const PEER_REJECTION_TIMEOUT: u32 = 3600;
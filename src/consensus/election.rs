@@ -0,0 +1,185 @@
+use crate::consensus::zrand::{prove_random_number, verify_receipt_binding};
+use crate::crypto::{ecvrf, signer::Signer};
+use crate::types::{ConsensusCommitment, ElectionProof};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use k256::ecdsa::VerifyingKey;
+use num_bigint::{BigInt, Sign};
+use serde::{Deserialize, Serialize};
+use zk_logic::random_bytes_to_int;
+
+// the leader election backend of a network, every node must use the same backend
+// since the proofs of one backend are rejected by the others
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ElectionBackend {
+    // predictable winners, only intended for tests
+    RoundRobin,
+    // RFC 9381 ECVRF over the secp256k1 validator keys
+    Ecvrf,
+    // the zk-vrf circuit, see `consensus::zrand`
+    Zk,
+}
+impl ElectionBackend {
+    pub fn leader_election(&self) -> Box<dyn LeaderElection> {
+        match self {
+            ElectionBackend::RoundRobin => Box::new(RoundRobinElection),
+            ElectionBackend::Ecvrf => Box::new(EcvrfElection),
+            ElectionBackend::Zk => Box::new(ZkElection),
+        }
+    }
+}
+
+// the round a commitment is made for, the random number must be bound to all three
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ElectionRound {
+    pub chain_id: String,
+    pub height: u32,
+    pub round: u32,
+}
+impl ElectionRound {
+    pub fn of(commitment: &ConsensusCommitment) -> Self {
+        Self {
+            chain_id: commitment.chain_id.clone(),
+            height: commitment.height,
+            round: commitment.round,
+        }
+    }
+    // the public input next to the validator key
+    pub fn seed(&self) -> Vec<u8> {
+        bincode::serialize(&(&self.chain_id, self.height, self.round)).unwrap()
+    }
+}
+
+// the committing validator proves a random number for the round, every node verifies
+// the proof and picks the round winner from the random number
+#[async_trait]
+pub trait LeaderElection: Send + Sync {
+    async fn prove(&self, signer: &dyn Signer, round: &ElectionRound) -> Result<ElectionProof>;
+    fn verify(
+        &self,
+        validator: &VerifyingKey,
+        round: &ElectionRound,
+        proof: &ElectionProof,
+    ) -> Result<BigInt>;
+}
+
+pub struct RoundRobinElection;
+#[async_trait]
+impl LeaderElection for RoundRobinElection {
+    async fn prove(&self, _: &dyn Signer, _: &ElectionRound) -> Result<ElectionProof> {
+        Ok(ElectionProof::RoundRobin)
+    }
+    fn verify(
+        &self,
+        _: &VerifyingKey,
+        round: &ElectionRound,
+        proof: &ElectionProof,
+    ) -> Result<BigInt> {
+        match proof {
+            ElectionProof::RoundRobin => Ok(BigInt::from(round.height) + round.round),
+            _ => bail!("Expected a round robin commitment"),
+        }
+    }
+}
+
+pub struct EcvrfElection;
+#[async_trait]
+impl LeaderElection for EcvrfElection {
+    async fn prove(&self, signer: &dyn Signer, round: &ElectionRound) -> Result<ElectionProof> {
+        Ok(ElectionProof::Ecvrf(signer.vrf_prove(round).await?))
+    }
+    fn verify(
+        &self,
+        validator: &VerifyingKey,
+        round: &ElectionRound,
+        proof: &ElectionProof,
+    ) -> Result<BigInt> {
+        match proof {
+            ElectionProof::Ecvrf(pi) => {
+                let beta = ecvrf::verify(validator, &round.seed(), pi)?;
+                Ok(BigInt::from_bytes_be(Sign::Plus, &beta))
+            }
+            _ => bail!("Expected an ECVRF commitment"),
+        }
+    }
+}
+
+pub struct ZkElection;
+#[async_trait]
+impl LeaderElection for ZkElection {
+    async fn prove(&self, signer: &dyn Signer, round: &ElectionRound) -> Result<ElectionProof> {
+        let public_key = signer.public_key().to_sec1_bytes().to_vec();
+        let seed = round.seed();
+        // proving takes minutes, it must not block a worker of the runtime serving the api
+        let receipt =
+            tokio::task::spawn_blocking(move || prove_random_number(public_key, seed)).await?;
+        Ok(ElectionProof::Zk(receipt))
+    }
+    fn verify(
+        &self,
+        validator: &VerifyingKey,
+        round: &ElectionRound,
+        proof: &ElectionProof,
+    ) -> Result<BigInt> {
+        match proof {
+            ElectionProof::Zk(receipt) => {
+                let outputs =
+                    verify_receipt_binding(receipt, &validator.to_sec1_bytes(), &round.seed())?;
+                Ok(random_bytes_to_int(&outputs.random_bytes))
+            }
+            _ => bail!("Expected a zk commitment"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ecvrf_election() {
+    use crate::crypto::{ecdsa::Keypair, signer::LocalSigner};
+    let keypair = Keypair::new();
+    let signer = LocalSigner::new(keypair.sk.clone());
+    let election = ElectionBackend::Ecvrf.leader_election();
+    let round = ElectionRound {
+        chain_id: "diseq-local-net".to_string(),
+        height: 1,
+        round: 1,
+    };
+    let proof = election.prove(&signer, &round).await.unwrap();
+    // the random number is the VRF output of the key for the round
+    let beta = ecvrf::verify(
+        &keypair.vk,
+        &round.seed(),
+        &ecvrf::prove(&keypair.sk, &round.seed()),
+    )
+    .unwrap();
+    assert_eq!(
+        election.verify(&keypair.vk, &round, &proof).unwrap(),
+        BigInt::from_bytes_be(Sign::Plus, &beta)
+    );
+    // a different key, alpha or proof fails
+    assert!(election.verify(&Keypair::new().vk, &round, &proof).is_err());
+    let next_round = ElectionRound {
+        round: 2,
+        ..round.clone()
+    };
+    assert!(election.verify(&keypair.vk, &next_round, &proof).is_err());
+    let other_key_proof = election
+        .prove(&LocalSigner::new(Keypair::new().sk), &round)
+        .await
+        .unwrap();
+    assert!(election
+        .verify(&keypair.vk, &round, &other_key_proof)
+        .is_err());
+    let ElectionProof::Ecvrf(mut pi) = proof.clone() else {
+        panic!("Expected an ECVRF proof");
+    };
+    let last = pi.len() - 1;
+    pi[last] ^= 1;
+    assert!(election
+        .verify(&keypair.vk, &round, &ElectionProof::Ecvrf(pi))
+        .is_err());
+    assert!(ElectionBackend::RoundRobin
+        .leader_election()
+        .verify(&keypair.vk, &next_round, &proof)
+        .is_err());
+}
@@ -1,33 +1,780 @@
-use crate::types::ConsensusCommitment;
-use crate::{config::consensus::ROUND_DURATION, get_current_time};
-use k256::ecdsa::VerifyingKey;
+use crate::consensus::{
+    election::{ElectionRound, LeaderElection},
+    quorum::QuorumRule,
+    validators::ValidatorSet,
+};
+use crate::get_current_time;
+use crate::types::{
+    Block, BlockHeader, ConsensusCommitment, FinalizedBlock, Message, QuorumCertificate,
+    TimeoutCertificate, TimeoutVote,
+};
+use anyhow::{anyhow, bail, Result};
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
 use num_bigint::BigInt;
 use num_traits::ToPrimitive;
-use zk_logic::{random_bytes_to_int, types::CircuitOutputs};
+use std::collections::HashMap;
+// verifies the election proof of the commitment and returns the round winner
 pub fn evaluate_commitment(
-    commitment: ConsensusCommitment,
-    validators: Vec<VerifyingKey>,
-) -> VerifyingKey {
-    let circuit_outputs: CircuitOutputs = commitment.receipt.journal.decode().unwrap();
-    choose_winner(
-        random_bytes_to_int(&circuit_outputs.random_bytes),
-        validators,
-    )
+    commitment: &ConsensusCommitment,
+    leader_election: &dyn LeaderElection,
+    validators: &ValidatorSet,
+) -> Result<VerifyingKey> {
+    let validator = VerifyingKey::from_sec1_bytes(&commitment.validator)
+        .map_err(|_| anyhow!("Invalid validator key"))?;
+    let random_number = leader_election.verify(
+        &validator,
+        &ElectionRound::of(commitment),
+        &commitment.proof,
+    )?;
+    Ok(choose_winner(random_number, validators))
+}
+// a commitment is only accepted from the committing validator of the round, it must be
+// signed by that validator and be made for the chain, the next height and the current round
+pub fn verify_commitment(
+    commitment: &ConsensusCommitment,
+    committing_validator: &VerifyingKey,
+    chain_id: &str,
+    next_height: u32,
+    round: u32,
+) -> Result<()> {
+    if commitment.chain_id != chain_id {
+        bail!(
+            "Commitment is for chain {}, expected {}",
+            commitment.chain_id,
+            chain_id
+        );
+    }
+    if commitment.height != next_height {
+        bail!(
+            "Stale commitment for height {}, expected {}",
+            commitment.height,
+            next_height
+        );
+    }
+    if commitment.round != round {
+        bail!(
+            "Stale commitment for round {}, current round is {}",
+            commitment.round,
+            round
+        );
+    }
+    let validator = VerifyingKey::from_sec1_bytes(&commitment.validator)
+        .map_err(|_| anyhow!("Invalid validator key"))?;
+    if &validator != committing_validator {
+        bail!("Commitment is not from the committing validator");
+    }
+    let signature = Signature::from_slice(&commitment.signature)
+        .map_err(|_| anyhow!("Invalid commitment signature"))?;
+    validator
+        .verify(&commitment.to_bytes(), &signature)
+        .map_err(|_| anyhow!("Commitment was not signed by the validator"))?;
+    Ok(())
 }
 pub fn get_committing_validator(
     last_block_unix_timestamp: u32,
-    validators: Vec<VerifyingKey>,
+    round_duration: u32,
+    validators: &ValidatorSet,
 ) -> VerifyingKey {
-    let round = current_round(last_block_unix_timestamp) - 1;
-    // returns the current validator
-    validators[round as usize % (validators.len() - 1)]
+    let round = current_round(last_block_unix_timestamp, round_duration) - 1;
+    committing_validator_for_round(round, validators)
+}
+// weighted round robin, a validator commits for as many consecutive rounds as it has stake
+fn committing_validator_for_round(round: u32, validators: &ValidatorSet) -> VerifyingKey {
+    validators.select_by_stake(round as u64)
+}
+// the proposer of a round after `view` view changes, walks the validator set starting at
+// the committing validator so that each view hands the round to a different validator
+pub fn fallback_proposer(round: u32, view: u32, validators: &ValidatorSet) -> VerifyingKey {
+    let committing_validator = committing_validator_for_round(round - 1, validators);
+    let position = validators
+        .validators
+        .iter()
+        .position(|validator| validator.public_key == committing_validator)
+        .expect("[Critical] Committing validator is part of the validator set");
+    validators.validators[(position + view as usize) % validators.validators.len()].public_key
+}
+// a timeout vote is only accepted from a validator of the set, for the next height, the
+// current round and the view following the current view
+pub fn verify_timeout_vote(
+    vote: &TimeoutVote,
+    validators: &ValidatorSet,
+    chain_id: &str,
+    next_height: u32,
+    round: u32,
+    view: u32,
+) -> Result<VerifyingKey> {
+    if vote.chain_id != chain_id || vote.height != next_height || vote.round != round {
+        bail!(
+            "Stale timeout vote for height {}, round {}, expected height {}, round {}",
+            vote.height,
+            vote.round,
+            next_height,
+            round
+        );
+    }
+    if vote.view != view + 1 {
+        bail!(
+            "Timeout vote for view {}, current view is {}",
+            vote.view,
+            view
+        );
+    }
+    let validator = VerifyingKey::from_sec1_bytes(&vote.validator)
+        .map_err(|_| anyhow!("Invalid validator key"))?;
+    if !validators.contains(&validator) {
+        bail!("Timeout vote is not from a validator");
+    }
+    let signature = Signature::from_slice(&vote.signature)
+        .map_err(|_| anyhow!("Invalid timeout vote signature"))?;
+    validator
+        .verify(&vote.to_bytes(), &signature)
+        .map_err(|_| anyhow!("Timeout vote was not signed by the validator"))?;
+    Ok(validator)
+}
+// a timeout certificate carries valid votes of a quorum for its view, for the next height
+// and the current round
+pub fn verify_timeout_certificate(
+    certificate: &TimeoutCertificate,
+    validators: &ValidatorSet,
+    quorum: &QuorumRule,
+    chain_id: &str,
+    next_height: u32,
+    round: u32,
+) -> Result<()> {
+    let previous_view = certificate
+        .view
+        .checked_sub(1)
+        .ok_or_else(|| anyhow!("Timeout certificate for the initial view"))?;
+    let mut voted: Vec<VerifyingKey> = Vec::new();
+    for vote in &certificate.votes {
+        let validator = verify_timeout_vote(
+            vote,
+            validators,
+            chain_id,
+            next_height,
+            round,
+            previous_view,
+        )?;
+        if !voted.contains(&validator) {
+            voted.push(validator);
+        }
+    }
+    let voting_power: u64 = voted
+        .iter()
+        .map(|validator| validators.stake_of(validator))
+        .sum();
+    let required_voting_power = quorum.required_voting_power(validators.total_stake());
+    if voting_power < required_voting_power {
+        bail!(
+            "Timeout certificate carries voting power {}, required: {}",
+            voting_power,
+            required_voting_power
+        );
+    }
+    Ok(())
+}
+// checks that the certificate belongs to the block and validator set and that its valid
+// signatures meet the quorum, the genesis Block is the only Block without a certificate
+pub fn verify_quorum_certificate(
+    header: &BlockHeader,
+    certificate: &QuorumCertificate,
+    validators: &ValidatorSet,
+    quorum: &QuorumRule,
+) -> Result<()> {
+    let block_hash = header.hash();
+    if certificate.block_hash != block_hash {
+        bail!("Quorum certificate is for a different Block");
+    }
+    if certificate.validator_set_id != validators.id() {
+        bail!("Quorum certificate is for a different validator set");
+    }
+    let mut signed: Vec<VerifyingKey> = Vec::new();
+    for commitment in &certificate.signatures {
+        let validator = VerifyingKey::from_sec1_bytes(&commitment.validator)
+            .map_err(|_| anyhow!("Invalid validator key in quorum certificate"))?;
+        if !validators.contains(&validator) {
+            bail!("Quorum certificate is signed by a non-validator");
+        }
+        let signature = Signature::from_slice(&commitment.signature)
+            .map_err(|_| anyhow!("Invalid signature in quorum certificate"))?;
+        validator
+            .verify(&block_hash, &signature)
+            .map_err(|_| anyhow!("Invalid signature in quorum certificate"))?;
+        if !signed.contains(&validator) {
+            signed.push(validator);
+        }
+    }
+    let voting_power: u64 = signed
+        .iter()
+        .map(|validator| validators.stake_of(validator))
+        .sum();
+    let required_voting_power = quorum.required_voting_power(validators.total_stake());
+    if voting_power < required_voting_power {
+        bail!(
+            "Quorum certificate carries voting power {}, required: {}",
+            voting_power,
+            required_voting_power
+        );
+    }
+    Ok(())
+}
+// the header must extend the parent, commit to the messages of the body and be signed
+// by its proposer, the state root can only be checked against the local trie
+pub fn verify_block_header(block: &Block, parent: &Block, validators: &ValidatorSet) -> Result<()> {
+    let header = &block.header;
+    if header.height != parent.header.height + 1 {
+        bail!(
+            "Block has height {}, expected {}",
+            header.height,
+            parent.header.height + 1
+        );
+    }
+    if header.timestamp < parent.header.timestamp {
+        bail!("Block timestamp is before its parent");
+    }
+    if header.parent_hash != parent.hash() {
+        bail!("Block does not link to its parent");
+    }
+    if header.messages_root != Block::messages_root(&block.messages) {
+        bail!("Block messages do not match the messages root");
+    }
+    let proposer = VerifyingKey::from_sec1_bytes(&header.proposer)
+        .map_err(|_| anyhow!("Invalid proposer key"))?;
+    if !validators.contains(&proposer) {
+        bail!("Block was proposed by a non-validator");
+    }
+    let signature = block
+        .signature
+        .as_ref()
+        .and_then(|signature| Signature::from_slice(signature).ok())
+        .ok_or_else(|| anyhow!("Block is not signed by its proposer"))?;
+    proposer
+        .verify(&block.hash(), &signature)
+        .map_err(|_| anyhow!("Invalid proposer signature"))
+}
+// a Block received from a peer must extend the local tip and be finalized by the validator set
+pub fn verify_finalized_block(
+    finalized_block: &FinalizedBlock,
+    parent: &Block,
+    validators: &ValidatorSet,
+    quorum: &QuorumRule,
+) -> Result<()> {
+    let block = &finalized_block.block;
+    verify_block_header(block, parent, validators)?;
+    verify_quorum_certificate(
+        &block.header,
+        &finalized_block.quorum_certificate,
+        validators,
+        quorum,
+    )
+}
+// signed messages must be signed for this chain and the nonces of a sender must increase,
+// starting above the last nonce of the sender in a finalized Block
+pub fn verify_block_messages(
+    messages: &[Message],
+    chain_id: &str,
+    last_nonce: impl Fn(&[u8]) -> Option<u64>,
+) -> Result<()> {
+    let mut nonces = HashMap::new();
+    for message in messages {
+        verify_message_nonce(message, chain_id, &mut nonces, &last_nonce)?;
+    }
+    Ok(())
+}
+// the messages a proposer may include, replayed and out of order messages are dropped
+pub fn ordered_messages(
+    messages: Vec<Message>,
+    chain_id: &str,
+    last_nonce: impl Fn(&[u8]) -> Option<u64>,
+) -> Vec<Message> {
+    let mut nonces = HashMap::new();
+    messages
+        .into_iter()
+        .filter(|message| verify_message_nonce(message, chain_id, &mut nonces, &last_nonce).is_ok())
+        .collect()
 }
-fn choose_winner(random_commitment: BigInt, validators: Vec<VerifyingKey>) -> VerifyingKey {
-    let index = (random_commitment % (validators.len() - 1))
-        .to_u32()
+fn verify_message_nonce(
+    message: &Message,
+    chain_id: &str,
+    nonces: &mut HashMap<Vec<u8>, u64>,
+    last_nonce: &impl Fn(&[u8]) -> Option<u64>,
+) -> Result<()> {
+    message.verify_envelope(chain_id)?;
+    let Some(envelope) = &message.envelope else {
+        return Ok(());
+    };
+    // earlier messages of the sender in the same Block count as well
+    let last = nonces
+        .get(&envelope.sender)
+        .copied()
+        .or_else(|| last_nonce(&envelope.sender));
+    if last.is_some_and(|last| envelope.nonce <= last) {
+        bail!(
+            "Message nonce {} does not exceed the last nonce of the sender",
+            envelope.nonce
+        );
+    }
+    nonces.insert(envelope.sender.clone(), envelope.nonce);
+    Ok(())
+}
+fn choose_winner(random_commitment: BigInt, validators: &ValidatorSet) -> VerifyingKey {
+    let target = (random_commitment % validators.total_stake())
+        .to_u64()
         .unwrap();
-    validators[index as usize]
+    validators.select_by_stake(target)
+}
+pub fn current_round(last_block_unix_timestamp: u32, round_duration: u32) -> u32 {
+    block_round(
+        get_current_time(),
+        last_block_unix_timestamp,
+        round_duration,
+    )
+}
+// the round in which a block with the given timestamp was proposed
+pub fn block_round(timestamp: u32, last_block_unix_timestamp: u32, round_duration: u32) -> u32 {
+    timestamp.saturating_sub(last_block_unix_timestamp) / (round_duration) + 1
 }
-pub fn current_round(last_block_unix_timestamp: u32) -> u32 {
-    (get_current_time() - last_block_unix_timestamp) / (ROUND_DURATION) + 1
+#[test]
+fn test_stake_weighted_selection() {
+    use crate::consensus::validators::Validator;
+    use crate::crypto::ecdsa::Keypair;
+    let (v1, v2) = (Keypair::new().vk, Keypair::new().vk);
+    let validators = ValidatorSet {
+        validators: vec![
+            Validator {
+                public_key: v1,
+                stake: 2,
+            },
+            Validator {
+                public_key: v2,
+                stake: 1,
+            },
+        ],
+    };
+    let committers: Vec<VerifyingKey> = (0..6)
+        .map(|round| committing_validator_for_round(round, &validators))
+        .collect();
+    assert_eq!(committers, vec![v1, v1, v2, v1, v1, v2]);
+    assert_eq!(choose_winner(BigInt::from(7), &validators), v1);
+    assert_eq!(choose_winner(BigInt::from(8), &validators), v2);
+}
+#[test]
+fn test_fallback_proposer() {
+    use crate::consensus::validators::Validator;
+    use crate::crypto::ecdsa::Keypair;
+    let (v1, v2, v3) = (Keypair::new().vk, Keypair::new().vk, Keypair::new().vk);
+    let validators = ValidatorSet {
+        validators: vec![
+            Validator {
+                public_key: v1,
+                stake: 2,
+            },
+            Validator {
+                public_key: v2,
+                stake: 1,
+            },
+            Validator {
+                public_key: v3,
+                stake: 1,
+            },
+        ],
+    };
+    // v1 commits in the second round, its stake does not make it the fallback again
+    assert_eq!(fallback_proposer(2, 0, &validators), v1);
+    assert_eq!(fallback_proposer(2, 1, &validators), v2);
+    assert_eq!(fallback_proposer(2, 2, &validators), v3);
+    assert_eq!(fallback_proposer(2, 3, &validators), v1);
+}
+#[test]
+fn test_verify_quorum_certificate() {
+    use crate::consensus::validators::Validator;
+    use crate::crypto::ecdsa::Keypair;
+    use crate::types::BlockCommitment;
+    let keypairs = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let validators = ValidatorSet {
+        validators: keypairs
+            .iter()
+            .map(|keypair| Validator {
+                public_key: keypair.vk,
+                stake: 1,
+            })
+            .collect(),
+    };
+    let block = Block {
+        header: BlockHeader {
+            height: 1,
+            parent_hash: vec![],
+            messages_root: Block::messages_root(&[]),
+            state_root: None,
+            message_index_root: vec![],
+            proposer: keypairs[0].serialize_vk(),
+            timestamp: 0,
+            view: 0,
+        },
+        messages: vec![],
+        signature: None,
+        commitments: None,
+    };
+    let sign = |keypair: &Keypair| BlockCommitment {
+        signature: keypair.sign_data(&block.hash()).to_bytes().to_vec(),
+        validator: keypair.serialize_vk(),
+        timestamp: 0,
+    };
+    let mut certificate = QuorumCertificate {
+        block_hash: block.hash(),
+        validator_set_id: validators.id(),
+        signatures: vec![sign(&keypairs[0]), sign(&keypairs[0])],
+    };
+    // a validator only counts once
+    assert!(verify_quorum_certificate(
+        &block.header,
+        &certificate,
+        &validators,
+        &QuorumRule::StrictMajority
+    )
+    .is_err());
+    certificate.signatures.push(sign(&keypairs[1]));
+    assert!(verify_quorum_certificate(
+        &block.header,
+        &certificate,
+        &validators,
+        &QuorumRule::StrictMajority
+    )
+    .is_ok());
+    let other_block = Block {
+        header: BlockHeader {
+            timestamp: 1,
+            ..block.header.clone()
+        },
+        ..block.clone()
+    };
+    assert!(verify_quorum_certificate(
+        &other_block.header,
+        &certificate,
+        &validators,
+        &QuorumRule::StrictMajority
+    )
+    .is_err());
+}
+
+#[test]
+fn test_verify_block_header() {
+    use crate::consensus::validators::Validator;
+    use crate::crypto::ecdsa::Keypair;
+    use crate::types::Message;
+    let keypair = Keypair::new();
+    let validators = ValidatorSet {
+        validators: vec![Validator {
+            public_key: keypair.vk,
+            stake: 1,
+        }],
+    };
+    let parent = Block::genesis(0);
+    let messages = vec![Message {
+        data: vec![1, 2, 3],
+        timestamp: 1,
+        envelope: None,
+    }];
+    let mut block = Block {
+        header: BlockHeader {
+            height: 1,
+            parent_hash: parent.hash(),
+            messages_root: Block::messages_root(&messages),
+            state_root: None,
+            message_index_root: vec![],
+            proposer: keypair.serialize_vk(),
+            timestamp: 1,
+            view: 0,
+        },
+        messages,
+        signature: None,
+        commitments: None,
+    };
+    // the proposer signature is required
+    assert!(verify_block_header(&block, &parent, &validators).is_err());
+    block.signature = Some(keypair.sign_data(&block.hash()).to_bytes().to_vec());
+    assert!(verify_block_header(&block, &parent, &validators).is_ok());
+    // the body must match the messages root
+    let mut tampered_block = block.clone();
+    tampered_block.messages[0].data = vec![4];
+    assert!(verify_block_header(&tampered_block, &parent, &validators).is_err());
+    // the header must link to the parent
+    let other_parent = Block {
+        header: BlockHeader {
+            timestamp: 1,
+            ..parent.header.clone()
+        },
+        ..parent.clone()
+    };
+    assert!(verify_block_header(&block, &other_parent, &validators).is_err());
+}
+
+#[test]
+fn test_verify_block_messages() {
+    use crate::crypto::ecdsa::Keypair;
+    let chain_id = "diseq-local-net";
+    let sender = Keypair::new();
+    let message = |nonce: u64| {
+        Message {
+            data: vec![1, 2, 3],
+            timestamp: 1,
+            envelope: None,
+        }
+        .sign(&sender, chain_id, nonce)
+    };
+    let anonymous = Message {
+        data: vec![4],
+        timestamp: 1,
+        envelope: None,
+    };
+    let finalized = |_: &[u8]| Some(2);
+    let messages = vec![message(3), anonymous.clone(), message(5)];
+    assert!(verify_block_messages(&messages, chain_id, finalized).is_ok());
+    // a message signed for another chain is rejected
+    assert!(verify_block_messages(&messages, "diseq-other-net", finalized).is_err());
+    // nonces must exceed the finalized nonce of the sender
+    assert!(verify_block_messages(&[message(2)], chain_id, finalized).is_err());
+    assert!(verify_block_messages(&[message(1)], chain_id, |_| None).is_ok());
+    // and increase within the Block
+    let replayed = vec![message(3), message(3)];
+    assert!(verify_block_messages(&replayed, chain_id, finalized).is_err());
+    let reordered = vec![message(5), message(3)];
+    assert!(verify_block_messages(&reordered, chain_id, finalized).is_err());
+    // a proposer drops the messages that would be rejected
+    let proposed = ordered_messages(
+        vec![message(5), anonymous, message(3), message(2), message(6)],
+        chain_id,
+        finalized,
+    );
+    let nonces: Vec<Option<u64>> = proposed
+        .iter()
+        .map(|message| message.envelope.as_ref().map(|envelope| envelope.nonce))
+        .collect();
+    assert_eq!(nonces, vec![Some(5), None, Some(6)]);
+}
+
+// proves with the mock prover, the real prover takes minutes per receipt
+#[cfg(feature = "mock-prover")]
+#[test]
+fn test_verify_commitment() {
+    use crate::consensus::{election::ElectionBackend, validators::Validator, zrand};
+    use crate::crypto::ecdsa::Keypair;
+    use crate::types::ElectionProof;
+    let (keypair, other_keypair) = (Keypair::new(), Keypair::new());
+    let validators = ValidatorSet {
+        validators: vec![
+            Validator {
+                public_key: keypair.vk,
+                stake: 1,
+            },
+            Validator {
+                public_key: other_keypair.vk,
+                stake: 1,
+            },
+        ],
+    };
+    let leader_election = ElectionBackend::Zk.leader_election();
+    let round = ElectionRound {
+        chain_id: "diseq-local-net".to_string(),
+        height: 1,
+        round: 1,
+    };
+    // a commitment for the round, the receipt is bound to the given validator and round
+    let commitment = |signer: &Keypair, bound_to: &Keypair, bound_round: &ElectionRound| {
+        let mut commitment = ConsensusCommitment {
+            validator: keypair.serialize_vk(),
+            chain_id: round.chain_id.clone(),
+            height: round.height,
+            round: round.round,
+            proof: ElectionProof::Zk(zrand::prove_random_number(
+                bound_to.serialize_vk(),
+                bound_round.seed(),
+            )),
+            signature: vec![],
+        };
+        commitment.signature = signer.sign_data(&commitment.to_bytes()).to_bytes().to_vec();
+        commitment
+    };
+    let check = |commitment: &ConsensusCommitment| {
+        verify_commitment(commitment, &keypair.vk, &round.chain_id, 1, 1)
+            .and_then(|_| evaluate_commitment(commitment, leader_election.as_ref(), &validators))
+    };
+    assert!(check(&commitment(&keypair, &keypair, &round)).is_ok());
+    // the receipt must be bound to the committing validator
+    assert!(check(&commitment(&keypair, &other_keypair, &round)).is_err());
+    // and to the height and the chain of the commitment
+    let other_height = ElectionRound {
+        height: 2,
+        ..round.clone()
+    };
+    assert!(check(&commitment(&keypair, &keypair, &other_height)).is_err());
+    let other_chain = ElectionRound {
+        chain_id: "diseq-other-net".to_string(),
+        ..round.clone()
+    };
+    assert!(check(&commitment(&keypair, &keypair, &other_chain)).is_err());
+    // a commitment for another height or chain is stale, even with a matching receipt
+    let mut stale_commitment = commitment(&keypair, &keypair, &other_height);
+    stale_commitment.height = 2;
+    assert!(check(&stale_commitment).is_err());
+    // the commitment must be signed by the committing validator
+    assert!(check(&commitment(&other_keypair, &keypair, &round)).is_err());
+    let mut tampered_commitment = commitment(&keypair, &keypair, &round);
+    tampered_commitment.round = 2;
+    assert!(verify_commitment(&tampered_commitment, &keypair.vk, &round.chain_id, 1, 2).is_err());
+}
+
+// the same checks with ECVRF proofs, so that they also run without the mock prover
+#[test]
+fn test_verify_commitment_ecvrf() {
+    use crate::consensus::{election::ElectionBackend, validators::Validator};
+    use crate::crypto::{ecdsa::Keypair, ecvrf};
+    use crate::types::ElectionProof;
+    let (keypair, other_keypair) = (Keypair::new(), Keypair::new());
+    let validators = ValidatorSet {
+        validators: vec![
+            Validator {
+                public_key: keypair.vk,
+                stake: 1,
+            },
+            Validator {
+                public_key: other_keypair.vk,
+                stake: 1,
+            },
+        ],
+    };
+    let leader_election = ElectionBackend::Ecvrf.leader_election();
+    let round = ElectionRound {
+        chain_id: "diseq-local-net".to_string(),
+        height: 1,
+        round: 1,
+    };
+    // a commitment of the validator for the round, proven by `prover` for `proven_round`
+    let commitment =
+        |validator: &Keypair, signer: &Keypair, prover: &Keypair, proven_round: &ElectionRound| {
+            let mut commitment = ConsensusCommitment {
+                validator: validator.serialize_vk(),
+                chain_id: round.chain_id.clone(),
+                height: round.height,
+                round: round.round,
+                proof: ElectionProof::Ecvrf(ecvrf::prove(&prover.sk, &proven_round.seed())),
+                signature: vec![],
+            };
+            commitment.signature = signer.sign_data(&commitment.to_bytes()).to_bytes().to_vec();
+            commitment
+        };
+    let check = |commitment: &ConsensusCommitment| {
+        verify_commitment(commitment, &keypair.vk, &round.chain_id, 1, 1)
+            .and_then(|_| evaluate_commitment(commitment, leader_election.as_ref(), &validators))
+    };
+    assert!(check(&commitment(&keypair, &keypair, &keypair, &round)).is_ok());
+    // a bad signature
+    assert!(check(&commitment(&keypair, &other_keypair, &keypair, &round)).is_err());
+    let mut unsigned_commitment = commitment(&keypair, &keypair, &keypair, &round);
+    unsigned_commitment.signature = vec![];
+    assert!(check(&unsigned_commitment).is_err());
+    // the wrong validator, signed and proven by itself or proven by another key
+    assert!(check(&commitment(
+        &other_keypair,
+        &other_keypair,
+        &other_keypair,
+        &round
+    ))
+    .is_err());
+    assert!(check(&commitment(&keypair, &keypair, &other_keypair, &round)).is_err());
+    // the proof must be for the height, round and chain of the commitment
+    for proven_round in [
+        ElectionRound {
+            height: 2,
+            ..round.clone()
+        },
+        ElectionRound {
+            round: 2,
+            ..round.clone()
+        },
+        ElectionRound {
+            chain_id: "diseq-other-net".to_string(),
+            ..round.clone()
+        },
+    ] {
+        assert!(check(&commitment(&keypair, &keypair, &keypair, &proven_round)).is_err());
+        // and the commitment itself is stale for another height, round or chain
+        let mut stale_commitment = commitment(&keypair, &keypair, &keypair, &proven_round);
+        stale_commitment.chain_id = proven_round.chain_id.clone();
+        stale_commitment.height = proven_round.height;
+        stale_commitment.round = proven_round.round;
+        stale_commitment.signature = keypair
+            .sign_data(&stale_commitment.to_bytes())
+            .to_bytes()
+            .to_vec();
+        assert!(check(&stale_commitment).is_err());
+        assert!(
+            evaluate_commitment(&stale_commitment, leader_election.as_ref(), &validators).is_ok()
+        );
+    }
+}
+
+#[test]
+fn test_verify_timeout_certificate() {
+    use crate::consensus::validators::Validator;
+    use crate::crypto::ecdsa::Keypair;
+    let keypairs = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let validators = ValidatorSet {
+        validators: keypairs
+            .iter()
+            .map(|keypair| Validator {
+                public_key: keypair.vk,
+                stake: 1,
+            })
+            .collect(),
+    };
+    let vote = |keypair: &Keypair, view: u32| {
+        let mut vote = TimeoutVote {
+            validator: keypair.serialize_vk(),
+            chain_id: "diseq-local-net".to_string(),
+            height: 1,
+            round: 1,
+            view,
+            signature: vec![],
+            certificate: None,
+        };
+        vote.signature = keypair.sign_data(&vote.to_bytes()).to_bytes().to_vec();
+        vote
+    };
+    let verify = |certificate: &TimeoutCertificate| {
+        verify_timeout_certificate(
+            certificate,
+            &validators,
+            &QuorumRule::StrictMajority,
+            "diseq-local-net",
+            1,
+            1,
+        )
+    };
+    let mut certificate = TimeoutCertificate {
+        height: 1,
+        round: 1,
+        view: 2,
+        votes: vec![vote(&keypairs[0], 2), vote(&keypairs[0], 2)],
+    };
+    // a validator only counts once
+    assert!(verify(&certificate).is_err());
+    certificate.votes.push(vote(&keypairs[1], 2));
+    assert!(verify(&certificate).is_ok());
+    // every vote must be for the view of the certificate
+    certificate.votes.push(vote(&keypairs[2], 3));
+    assert!(verify(&certificate).is_err());
+    certificate.votes.pop();
+    certificate.votes[2].signature = certificate.votes[0].signature.clone();
+    assert!(verify(&certificate).is_err());
+    // and for the next height and the current round
+    assert!(verify_timeout_certificate(
+        &certificate,
+        &validators,
+        &QuorumRule::StrictMajority,
+        "diseq-local-net",
+        2,
+        1,
+    )
+    .is_err());
 }
@@ -1,2 +1,5 @@
+pub mod election;
 pub mod logic;
+pub mod quorum;
+pub mod validators;
 pub mod zrand;
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+// the voting power required to finalize a block, derived from the total voting power
+// of the validator set so that validators can be added or removed in the genesis file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuorumRule {
+    // more than half of the total voting power
+    StrictMajority,
+    // 2f+1 out of 3f+1, tolerates f faulty validators
+    Bft,
+    // more than numerator / denominator of the total voting power
+    Fraction { numerator: u64, denominator: u64 },
+}
+impl QuorumRule {
+    pub fn required_voting_power(&self, total_voting_power: u64) -> u64 {
+        match self {
+            QuorumRule::StrictMajority => total_voting_power / 2 + 1,
+            QuorumRule::Bft => total_voting_power - total_voting_power.saturating_sub(1) / 3,
+            QuorumRule::Fraction {
+                numerator,
+                denominator,
+            } => {
+                // computed in u128, the product of large stakes and the numerator overflows u64
+                let required =
+                    total_voting_power as u128 * *numerator as u128 / *denominator as u128;
+                // the numerator is below the denominator, the quotient is below the total
+                required as u64 + 1
+            }
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        match self {
+            QuorumRule::Fraction {
+                numerator,
+                denominator,
+            } => *denominator > 0 && numerator < denominator,
+            _ => true,
+        }
+    }
+}
+
+#[test]
+fn test_required_voting_power() {
+    assert_eq!(QuorumRule::StrictMajority.required_voting_power(4), 3);
+    assert_eq!(QuorumRule::StrictMajority.required_voting_power(5), 3);
+    assert_eq!(QuorumRule::Bft.required_voting_power(4), 3);
+    assert_eq!(QuorumRule::Bft.required_voting_power(7), 5);
+    assert_eq!(QuorumRule::Bft.required_voting_power(1), 1);
+    let two_thirds = QuorumRule::Fraction {
+        numerator: 2,
+        denominator: 3,
+    };
+    assert_eq!(two_thirds.required_voting_power(6), 5);
+    // stakes close to u64::MAX don't overflow
+    let total_voting_power = u64::MAX - 1;
+    assert_eq!(
+        two_thirds.required_voting_power(total_voting_power),
+        12297829382473034410
+    );
+}
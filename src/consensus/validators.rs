@@ -0,0 +1,85 @@
+use k256::ecdsa::VerifyingKey;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validator {
+    pub public_key: VerifyingKey,
+    pub stake: u64,
+}
+
+// validators in genesis order, the order is part of consensus since
+// selection walks the cumulative stake in this order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatorSet {
+    pub validators: Vec<Validator>,
+}
+impl ValidatorSet {
+    pub fn contains(&self, public_key: &VerifyingKey) -> bool {
+        self.validators
+            .iter()
+            .any(|validator| &validator.public_key == public_key)
+    }
+    pub fn stake_of(&self, public_key: &VerifyingKey) -> u64 {
+        self.validators
+            .iter()
+            .find(|validator| &validator.public_key == public_key)
+            .map(|validator| validator.stake)
+            .unwrap_or(0)
+    }
+    // identifies the validator set a quorum certificate was formed by
+    pub fn id(&self) -> Vec<u8> {
+        let validators: Vec<(Vec<u8>, u64)> = self
+            .validators
+            .iter()
+            .map(|validator| {
+                (
+                    validator.public_key.to_sec1_bytes().to_vec(),
+                    validator.stake,
+                )
+            })
+            .collect();
+        Sha256::digest(bincode::serialize(&validators).unwrap()).to_vec()
+    }
+    pub fn total_stake(&self) -> u64 {
+        self.validators
+            .iter()
+            .map(|validator| validator.stake)
+            .sum()
+    }
+    // returns the validator whose stake range contains the target, where
+    // validator i owns [stake_0 + .. + stake_i-1, stake_0 + .. + stake_i)
+    pub fn select_by_stake(&self, target: u64) -> VerifyingKey {
+        let target = target % self.total_stake();
+        let mut cumulative_stake: u64 = 0;
+        for validator in &self.validators {
+            cumulative_stake += validator.stake;
+            if target < cumulative_stake {
+                return validator.public_key;
+            }
+        }
+        unreachable!("[Critical] Target is always below the total stake")
+    }
+}
+
+#[test]
+fn test_select_by_stake() {
+    use crate::crypto::ecdsa::Keypair;
+    let (v1, v2) = (Keypair::new().vk, Keypair::new().vk);
+    let validator_set = ValidatorSet {
+        validators: vec![
+            Validator {
+                public_key: v1,
+                stake: 1,
+            },
+            Validator {
+                public_key: v2,
+                stake: 3,
+            },
+        ],
+    };
+    assert_eq!(validator_set.total_stake(), 4);
+    assert_eq!(validator_set.select_by_stake(0), v1);
+    assert_eq!(validator_set.select_by_stake(1), v2);
+    assert_eq!(validator_set.select_by_stake(3), v2);
+    assert_eq!(validator_set.select_by_stake(4), v1);
+}
@@ -1,10 +1,55 @@
+use anyhow::{anyhow, bail, Result};
 use prover::ZK_RAND_ID;
 use risc0_zkvm::Receipt;
 use zk_logic::random_bytes_to_int;
 use zk_logic::types::CircuitOutputs;
+#[cfg(not(feature = "mock-prover"))]
+pub fn prove_random_number(public_key: Vec<u8>, seed: Vec<u8>) -> Receipt {
+    prover::generate_random_number(public_key, seed)
+}
+// the mock prover skips proving and wraps the journal the circuit would commit to in
+// a fake receipt, the random bytes are the hash of the inputs instead of the circuit output
+#[cfg(feature = "mock-prover")]
+pub fn prove_random_number(public_key: Vec<u8>, seed: Vec<u8>) -> Receipt {
+    use risc0_zkvm::{FakeReceipt, InnerReceipt, ReceiptClaim};
+    use sha2::{Digest, Sha256};
+    let random_bytes = Sha256::new()
+        .chain_update(&public_key)
+        .chain_update(&seed)
+        .finalize()
+        .to_vec();
+    let outputs = CircuitOutputs {
+        public_key,
+        seed,
+        random_bytes,
+    };
+    let journal: Vec<u8> = risc0_zkvm::serde::to_vec(&outputs)
+        .expect("Failed to encode mock journal")
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+    Receipt::new(
+        InnerReceipt::Fake(FakeReceipt::new(ReceiptClaim::ok(
+            ZK_RAND_ID,
+            journal.clone(),
+        ))),
+        journal,
+    )
+}
+// receipts of the mock prover can't be verified, only the journal is checked in that mode
+#[cfg(not(feature = "mock-prover"))]
+fn verify_receipt(receipt: &Receipt) -> Result<()> {
+    receipt
+        .verify(ZK_RAND_ID)
+        .map_err(|e| anyhow!("Invalid random number receipt: {}", e))
+}
+#[cfg(feature = "mock-prover")]
+fn verify_receipt(_: &Receipt) -> Result<()> {
+    Ok(())
+}
 #[allow(unused)]
 pub fn verify_random_number(receipt: Receipt) -> u32 {
-    receipt.verify(ZK_RAND_ID).expect("Invalid Random Number");
+    verify_receipt(&receipt).expect("Invalid Random Number");
     let outputs: CircuitOutputs = receipt.journal.decode().unwrap();
     *random_bytes_to_int(&outputs.random_bytes)
         .to_u32_digits()
@@ -12,10 +57,28 @@ pub fn verify_random_number(receipt: Receipt) -> u32 {
         .last()
         .unwrap()
 }
+// verifies the receipt and that its journal was generated for this validator and seed
+pub fn verify_receipt_binding(
+    receipt: &Receipt,
+    validator: &[u8],
+    seed: &[u8],
+) -> Result<CircuitOutputs> {
+    verify_receipt(receipt)?;
+    let outputs: CircuitOutputs = receipt
+        .journal
+        .decode()
+        .map_err(|e| anyhow!("Invalid random number journal: {}", e))?;
+    if outputs.public_key != validator {
+        bail!("Random number was generated for a different validator");
+    }
+    if outputs.seed != seed {
+        bail!("Random number was generated for a different seed");
+    }
+    Ok(outputs)
+}
 #[test]
 fn test_verify_random_number() {
-    use prover::generate_random_number;
-    let random_number: Receipt = generate_random_number(vec![0; 32], vec![0; 32]);
+    let random_number: Receipt = prove_random_number(vec![0; 32], vec![0; 32]);
     let result = verify_random_number(random_number);
-    println!("Random u32: {:?}", &result);
+    println!("Random u32: {:?}", result);
 }
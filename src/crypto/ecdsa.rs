@@ -30,6 +30,7 @@ impl Default for Keypair {
         Self::new()
     }
 }
+#[allow(unused)]
 pub fn deserialize_sk(sk_serialized: &[u8]) -> SigningKey {
     SigningKey::from_bytes(sk_serialized.into()).unwrap()
 }
@@ -0,0 +1,142 @@
+use anyhow::{anyhow, bail, Result};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use k256::elliptic_curve::{
+    bigint::ArrayEncoding,
+    ops::Reduce,
+    sec1::{FromEncodedPoint, ToEncodedPoint},
+    Curve, PrimeField,
+};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, Secp256k1, U256};
+use sha2::{Digest, Sha256};
+
+// ECVRF as specified in RFC 9381, instantiated over secp256k1 with SHA-256, RFC 6979 nonces
+// and the try-and-increment encode to curve, mirroring the ECVRF-P256-SHA256-TAI suite
+const SUITE_STRING: u8 = 0xfe;
+const C_LEN: usize = 16;
+const PT_LEN: usize = 33;
+const Q_LEN: usize = 32;
+pub const PROOF_LEN: usize = PT_LEN + C_LEN + Q_LEN;
+
+// returns pi = Gamma || c || s for the input alpha
+pub fn prove(signing_key: &SigningKey, alpha: &[u8]) -> Vec<u8> {
+    let x: Scalar = *signing_key.as_nonzero_scalar().as_ref();
+    let y = ProjectivePoint::GENERATOR * x;
+    let h = encode_to_curve(&point_to_string(&y), alpha);
+    let gamma = h * x;
+    let k = nonce_generation(signing_key, &point_to_string(&h));
+    let c = challenge_generation(&[y, h, gamma, ProjectivePoint::GENERATOR * k, h * k]);
+    let s = k + c * x;
+    let mut pi = point_to_string(&gamma);
+    pi.extend_from_slice(&c.to_bytes()[Q_LEN - C_LEN..]);
+    pi.extend_from_slice(&s.to_bytes());
+    pi
+}
+
+// returns the VRF output beta if pi is a valid proof for alpha under the public key
+pub fn verify(public_key: &VerifyingKey, alpha: &[u8], pi: &[u8]) -> Result<[u8; 32]> {
+    if pi.len() != PROOF_LEN {
+        bail!("Invalid VRF proof length: {}", pi.len());
+    }
+    let gamma = string_to_point(&pi[..PT_LEN]).ok_or_else(|| anyhow!("Invalid VRF proof point"))?;
+    let c = string_to_scalar(&pi[PT_LEN..PT_LEN + C_LEN])
+        .ok_or_else(|| anyhow!("Invalid VRF proof challenge"))?;
+    let s = string_to_scalar(&pi[PT_LEN + C_LEN..])
+        .ok_or_else(|| anyhow!("Invalid VRF proof scalar"))?;
+    let y = ProjectivePoint::from(*public_key.as_affine());
+    let h = encode_to_curve(&point_to_string(&y), alpha);
+    let u = ProjectivePoint::GENERATOR * s - y * c;
+    let v = h * s - gamma * c;
+    if challenge_generation(&[y, h, gamma, u, v]) != c {
+        bail!("Invalid VRF proof");
+    }
+    Ok(proof_to_hash(&gamma))
+}
+
+fn proof_to_hash(gamma: &ProjectivePoint) -> [u8; 32] {
+    // the cofactor of secp256k1 is 1
+    Sha256::new()
+        .chain_update([SUITE_STRING, 0x03])
+        .chain_update(point_to_string(gamma))
+        .chain_update([0x00])
+        .finalize()
+        .into()
+}
+
+fn encode_to_curve(encode_to_curve_salt: &[u8], alpha: &[u8]) -> ProjectivePoint {
+    for ctr in 0..=u8::MAX {
+        let hash_string = Sha256::new()
+            .chain_update([SUITE_STRING, 0x01])
+            .chain_update(encode_to_curve_salt)
+            .chain_update(alpha)
+            .chain_update([ctr, 0x00])
+            .finalize();
+        let mut point_string = vec![0x02];
+        point_string.extend_from_slice(&hash_string);
+        if let Some(h) = string_to_point(&point_string) {
+            return h;
+        }
+    }
+    // roughly half of all candidates are valid x coordinates
+    unreachable!("[Critical] Failed to encode VRF input to the curve")
+}
+
+fn nonce_generation(signing_key: &SigningKey, h_string: &[u8]) -> Scalar {
+    let h1 = <Scalar as Reduce<U256>>::reduce_bytes(&Sha256::digest(h_string)).to_bytes();
+    let k = rfc6979::generate_k::<Sha256, _>(
+        &signing_key.to_bytes(),
+        &Secp256k1::ORDER.to_be_byte_array(),
+        &h1,
+        &[],
+    );
+    Option::from(Scalar::from_repr(k)).expect("[Critical] RFC 6979 nonce is always below the order")
+}
+
+fn challenge_generation(points: &[ProjectivePoint]) -> Scalar {
+    let mut hasher = Sha256::new().chain_update([SUITE_STRING, 0x02]);
+    for point in points {
+        hasher.update(point_to_string(point));
+    }
+    let c_string = hasher.chain_update([0x00]).finalize();
+    string_to_scalar(&c_string[..C_LEN]).expect("[Critical] Challenge is always below the order")
+}
+
+fn point_to_string(point: &ProjectivePoint) -> Vec<u8> {
+    point.to_affine().to_encoded_point(true).as_bytes().to_vec()
+}
+
+fn string_to_point(point_string: &[u8]) -> Option<ProjectivePoint> {
+    if point_string.len() != PT_LEN {
+        return None;
+    }
+    let encoded_point = EncodedPoint::from_bytes(point_string).ok()?;
+    Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded_point))
+        .map(ProjectivePoint::from)
+}
+
+// interprets up to Q_LEN big endian bytes as a scalar, rejecting values above the order
+fn string_to_scalar(scalar_string: &[u8]) -> Option<Scalar> {
+    let mut repr = FieldBytes::default();
+    repr[Q_LEN - scalar_string.len()..].copy_from_slice(scalar_string);
+    Option::from(Scalar::from_repr(repr))
+}
+
+#[test]
+fn test_ecvrf_prove_and_verify() {
+    use crate::crypto::ecdsa::Keypair;
+    let keypair = Keypair::new();
+    let alpha = b"diseq".to_vec();
+    let pi = prove(&keypair.sk, &alpha);
+    assert_eq!(pi.len(), PROOF_LEN);
+    // the proof and output are deterministic
+    assert_eq!(pi, prove(&keypair.sk, &alpha));
+    let beta = verify(&keypair.vk, &alpha, &pi).expect("Failed to verify VRF proof");
+    assert_eq!(
+        beta,
+        proof_to_hash(&string_to_point(&pi[..PT_LEN]).unwrap())
+    );
+    assert!(verify(&keypair.vk, b"other", &pi).is_err());
+    assert!(verify(&Keypair::new().vk, &alpha, &pi).is_err());
+    let mut tampered_pi = pi.clone();
+    tampered_pi[PROOF_LEN - 1] ^= 1;
+    assert!(verify(&keypair.vk, &alpha, &tampered_pi).is_err());
+}
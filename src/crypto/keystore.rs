@@ -0,0 +1,122 @@
+use crate::crypto::ecdsa::{deserialize_vk, Keypair};
+use crate::types::GenericPublicKey;
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit},
+    Aes256Gcm, Key,
+};
+use anyhow::{anyhow, bail, Result};
+use k256::ecdsa::SigningKey;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+pub const KEYSTORE_VERSION: u32 = 1;
+// scrypt cost parameters for newly created keystores
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    pub salt: Vec<u8>,
+}
+
+// a validator signing key encrypted with a passphrase derived key (scrypt + aes-256-gcm),
+// the public key is stored in plain so that operators can add it to the genesis file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Keystore {
+    pub version: u32,
+    pub public_key: GenericPublicKey,
+    pub kdf: KdfParams,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+impl Keystore {
+    #[allow(unused)]
+    pub fn encrypt(keypair: &Keypair, passphrase: &str) -> Result<Keystore> {
+        let mut salt = vec![0u8; 32];
+        OsRng.fill_bytes(&mut salt);
+        let kdf = KdfParams {
+            log_n: SCRYPT_LOG_N,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt,
+        };
+        let cipher = Aes256Gcm::new(&derive_key(&kdf, passphrase)?);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, keypair.serialize_sk().as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt signing key"))?;
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            public_key: keypair.serialize_vk(),
+            kdf,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+    pub fn decrypt(&self, passphrase: &str) -> Result<Keypair> {
+        if self.version != KEYSTORE_VERSION {
+            bail!("Unsupported keystore version: {}", self.version);
+        }
+        let nonce: [u8; 12] = self
+            .nonce
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Invalid keystore nonce"))?;
+        let cipher = Aes256Gcm::new(&derive_key(&self.kdf, passphrase)?);
+        let sk_serialized = cipher
+            .decrypt(&nonce.into(), self.ciphertext.as_slice())
+            .map_err(|_| anyhow!("Failed to decrypt keystore, wrong passphrase?"))?;
+        let sk = SigningKey::from_slice(&sk_serialized)?;
+        let vk = *sk.verifying_key();
+        if vk != deserialize_vk(&self.public_key) {
+            bail!("Keystore public key does not match the decrypted signing key");
+        }
+        Ok(Keypair { sk, vk })
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Keystore> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+    #[allow(unused)]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if path.as_ref().exists() {
+            bail!("Refusing to overwrite existing keystore");
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// generates a new validator key and writes it to an encrypted keystore file
+#[allow(unused)]
+pub fn generate_keystore(path: impl AsRef<Path>, passphrase: &str) -> Result<Keypair> {
+    let keypair = Keypair::new();
+    Keystore::encrypt(&keypair, passphrase)?.save(path)?;
+    Ok(keypair)
+}
+
+pub fn load_keystore(path: impl AsRef<Path>, passphrase: &str) -> Result<Keypair> {
+    Keystore::load(path)?.decrypt(passphrase)
+}
+
+fn derive_key(kdf: &KdfParams, passphrase: &str) -> Result<Key<Aes256Gcm>> {
+    let params = scrypt::Params::new(kdf.log_n, kdf.r, kdf.p, 32)
+        .map_err(|e| anyhow!("Invalid scrypt parameters: {}", e))?;
+    let mut key = Key::<Aes256Gcm>::default();
+    scrypt::scrypt(passphrase.as_bytes(), &kdf.salt, &params, &mut key)
+        .map_err(|e| anyhow!("Failed to derive keystore key: {}", e))?;
+    Ok(key)
+}
+
+#[test]
+fn test_encrypt_and_decrypt_keystore() {
+    let keypair = Keypair::new();
+    let keystore = Keystore::encrypt(&keypair, "passphrase").unwrap();
+    let decrypted = keystore.decrypt("passphrase").unwrap();
+    assert_eq!(keypair.sk, decrypted.sk);
+    assert!(keystore.decrypt("wrong passphrase").is_err());
+}
@@ -0,0 +1,61 @@
+use sha2::{Digest, Sha256};
+
+// binary sha256 merkle tree over an ordered list of leaves, leaves and inner nodes are
+// domain separated like in RFC 6962 and an odd node is carried up to the next level
+// unchanged, so that no two lists share a root
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn hash_leaf(leaf: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update(leaf)
+        .finalize()
+        .to_vec()
+}
+
+fn hash_node(left: &[u8], right: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([NODE_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .to_vec()
+}
+
+// the root of an empty list is the hash of no data
+pub fn merkle_root(leaves: &[Vec<u8>]) -> Vec<u8> {
+    if leaves.is_empty() {
+        return Sha256::digest([]).to_vec();
+    }
+    let mut level: Vec<Vec<u8>> = leaves.iter().map(|leaf| hash_leaf(leaf)).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [odd] => odd.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level.remove(0)
+}
+
+#[test]
+fn test_merkle_root() {
+    let leaves: Vec<Vec<u8>> = (0u8..5).map(|i| vec![i]).collect();
+    let root = merkle_root(&leaves);
+    assert_eq!(root, merkle_root(&leaves));
+    let left = hash_node(
+        &hash_node(&hash_leaf(&[0]), &hash_leaf(&[1])),
+        &hash_node(&hash_leaf(&[2]), &hash_leaf(&[3])),
+    );
+    assert_eq!(root, hash_node(&left, &hash_leaf(&[4])));
+    // reordering or dropping a leaf changes the root
+    let mut reordered = leaves.clone();
+    reordered.swap(0, 1);
+    assert_ne!(root, merkle_root(&reordered));
+    assert_ne!(root, merkle_root(&leaves[..4]));
+    assert_ne!(merkle_root(&[]), merkle_root(&[vec![]]));
+}
@@ -1 +1,6 @@
 pub mod ecdsa;
+pub mod ecvrf;
+pub mod keystore;
+pub mod merkle;
+pub mod signer;
+pub mod sparse_merkle;
@@ -0,0 +1,168 @@
+use crate::consensus::election::ElectionRound;
+use crate::crypto::ecvrf;
+use crate::types::{
+    BlockHeader, ConsensusCommitment, GenericPublicKey, GenericSignature, TimeoutVote,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use k256::ecdsa::{
+    signature::{Signer as _, Verifier},
+    Signature, SigningKey, VerifyingKey,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// all consensus signatures are produced through this trait, so that the validator key
+// does not have to live inside the sequencer process
+#[async_trait]
+pub trait Signer: Send + Sync {
+    fn public_key(&self) -> VerifyingKey;
+    async fn sign(&self, request: &SignRequest) -> Result<Signature>;
+    // an ECVRF proof over the seed of the election round, see `crypto::ecvrf`
+    async fn vrf_prove(&self, round: &ElectionRound) -> Result<Vec<u8>>;
+}
+
+pub struct LocalSigner {
+    signing_key: SigningKey,
+}
+impl LocalSigner {
+    pub fn new(signing_key: SigningKey) -> Self {
+        Self { signing_key }
+    }
+}
+#[async_trait]
+impl Signer for LocalSigner {
+    fn public_key(&self) -> VerifyingKey {
+        *self.signing_key.verifying_key()
+    }
+    async fn sign(&self, request: &SignRequest) -> Result<Signature> {
+        Ok(self.signing_key.sign(&request.signing_bytes()))
+    }
+    async fn vrf_prove(&self, round: &ElectionRound) -> Result<Vec<u8>> {
+        Ok(ecvrf::prove(&self.signing_key, &round.seed()))
+    }
+}
+
+// the only payloads a validator key signs, the signer derives the signed bytes itself
+// so that it can't be used to sign arbitrary data
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SignRequest {
+    // proposals and Block commitments sign the header hash
+    Header(BlockHeader),
+    Commitment(ConsensusCommitment),
+    TimeoutVote(TimeoutVote),
+}
+impl SignRequest {
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        match self {
+            SignRequest::Header(header) => header.hash(),
+            SignRequest::Commitment(commitment) => commitment.to_bytes(),
+            SignRequest::TimeoutVote(vote) => vote.to_bytes(),
+        }
+    }
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VrfRequest {
+    pub round: ElectionRound,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignResponse {
+    pub signature: GenericSignature,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VrfResponse {
+    pub proof: Vec<u8>,
+}
+
+// talks to a separate signer process over http, see `src/bin/signer.rs`. Every request
+// carries the token the signer was started with
+pub struct RemoteSigner {
+    url: String,
+    token: String,
+    client: Client,
+    public_key: VerifyingKey,
+}
+impl RemoteSigner {
+    pub async fn connect(url: &str, token: &str) -> Result<Self> {
+        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        let public_key_json = client
+            .get(format!("{}{}", url, "/public_key"))
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let public_key: GenericPublicKey = serde_json::from_str(&public_key_json)?;
+        Ok(Self {
+            url: url.to_string(),
+            token: token.to_string(),
+            client,
+            public_key: VerifyingKey::from_sec1_bytes(&public_key)?,
+        })
+    }
+}
+#[async_trait]
+impl Signer for RemoteSigner {
+    fn public_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+    async fn sign(&self, request: &SignRequest) -> Result<Signature> {
+        let response_json = self
+            .client
+            .post(format!("{}{}", self.url, "/sign"))
+            .bearer_auth(&self.token)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&request)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response: SignResponse = serde_json::from_str(&response_json)?;
+        let signature = Signature::from_slice(&response.signature)?;
+        // never hand out a signature the remote signer produced with a different key
+        self.public_key
+            .verify(&request.signing_bytes(), &signature)
+            .map_err(|_| anyhow!("Remote signer returned an invalid signature"))?;
+        Ok(signature)
+    }
+    async fn vrf_prove(&self, round: &ElectionRound) -> Result<Vec<u8>> {
+        let request = VrfRequest {
+            round: round.clone(),
+        };
+        let response_json = self
+            .client
+            .post(format!("{}{}", self.url, "/vrf_prove"))
+            .bearer_auth(&self.token)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&request)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response: VrfResponse = serde_json::from_str(&response_json)?;
+        ecvrf::verify(&self.public_key, &round.seed(), &response.proof)
+            .map_err(|_| anyhow!("Remote signer returned an invalid VRF proof"))?;
+        Ok(response.proof)
+    }
+}
+
+#[tokio::test]
+async fn test_local_signer() {
+    use crate::crypto::ecdsa::Keypair;
+    use crate::types::Block;
+    let keypair = Keypair::new();
+    let signer = LocalSigner::new(keypair.sk.clone());
+    let header = Block::genesis(0).header;
+    let signature = signer
+        .sign(&SignRequest::Header(header.clone()))
+        .await
+        .unwrap();
+    keypair
+        .vk
+        .verify(&header.hash(), &signature)
+        .expect("Failed to verify signature");
+}
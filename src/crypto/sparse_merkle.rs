@@ -0,0 +1,321 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// sparse merkle tree over 256 bit keys, a subtree that holds a single leaf is represented
+// by the leaf itself, so a key is stored at the depth where it first differs from all
+// other keys. Nodes are content addressed and never removed, every past root stays provable
+pub const EMPTY_ROOT: [u8; 32] = [0; 32];
+const KEY_BITS: usize = 256;
+const LEAF_PREFIX: u8 = 0x00;
+const INTERNAL_PREFIX: u8 = 0x01;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SmtNode {
+    Internal { left: Vec<u8>, right: Vec<u8> },
+    Leaf { key: Vec<u8>, value_hash: Vec<u8> },
+}
+impl SmtNode {
+    pub fn hash(&self) -> Vec<u8> {
+        match self {
+            SmtNode::Internal { left, right } => hash_internal(left, right),
+            SmtNode::Leaf { key, value_hash } => hash_leaf(key, value_hash),
+        }
+    }
+}
+
+pub trait SmtStore {
+    fn get_node(&self, hash: &[u8]) -> Option<SmtNode>;
+    fn insert_node(&mut self, hash: &[u8], node: &SmtNode);
+}
+
+// the siblings on the path of a key from the root down, and the leaf the path ends in.
+// The leaf is either the key itself, a different key that shares the path, or no leaf
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SparseMerkleProof {
+    pub leaf: Option<(Vec<u8>, Vec<u8>)>,
+    pub siblings: Vec<Vec<u8>>,
+}
+
+fn hash_leaf(key: &[u8], value_hash: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update(key)
+        .chain_update(value_hash)
+        .finalize()
+        .to_vec()
+}
+
+fn hash_internal(left: &[u8], right: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([INTERNAL_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .to_vec()
+}
+
+fn bit(key: &[u8], depth: usize) -> bool {
+    (key[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+fn store_node(store: &mut dyn SmtStore, node: SmtNode) -> Vec<u8> {
+    let hash = node.hash();
+    store.insert_node(&hash, &node);
+    hash
+}
+
+// inserts the key unless it is already present, the first value of a key is kept
+pub fn insert(store: &mut dyn SmtStore, root: &[u8], key: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+    if key.len() * 8 != KEY_BITS {
+        bail!("Sparse merkle keys must be 32 bytes");
+    }
+    let leaf = SmtNode::Leaf {
+        key: key.to_vec(),
+        value_hash: Sha256::digest(value).to_vec(),
+    };
+    insert_at(store, root, leaf, key, 0)
+}
+
+fn insert_at(
+    store: &mut dyn SmtStore,
+    node_hash: &[u8],
+    leaf: SmtNode,
+    key: &[u8],
+    depth: usize,
+) -> Result<Vec<u8>> {
+    if node_hash == EMPTY_ROOT {
+        return Ok(store_node(store, leaf));
+    }
+    match store.get_node(node_hash) {
+        Some(SmtNode::Leaf {
+            key: existing_key, ..
+        }) => {
+            if existing_key == key {
+                return Ok(node_hash.to_vec());
+            }
+            Ok(split(store, node_hash, &existing_key, leaf, key, depth))
+        }
+        Some(SmtNode::Internal { left, right }) => {
+            let node = if bit(key, depth) {
+                SmtNode::Internal {
+                    right: insert_at(store, &right, leaf, key, depth + 1)?,
+                    left,
+                }
+            } else {
+                SmtNode::Internal {
+                    left: insert_at(store, &left, leaf, key, depth + 1)?,
+                    right,
+                }
+            };
+            Ok(store_node(store, node))
+        }
+        None => bail!("Missing sparse merkle node"),
+    }
+}
+
+// replaces a leaf by the internal nodes down to the depth where both keys differ
+fn split(
+    store: &mut dyn SmtStore,
+    existing_hash: &[u8],
+    existing_key: &[u8],
+    leaf: SmtNode,
+    key: &[u8],
+    depth: usize,
+) -> Vec<u8> {
+    let node = if bit(key, depth) == bit(existing_key, depth) {
+        let child = split(store, existing_hash, existing_key, leaf, key, depth + 1);
+        if bit(key, depth) {
+            SmtNode::Internal {
+                left: EMPTY_ROOT.to_vec(),
+                right: child,
+            }
+        } else {
+            SmtNode::Internal {
+                left: child,
+                right: EMPTY_ROOT.to_vec(),
+            }
+        }
+    } else {
+        let leaf_hash = store_node(store, leaf);
+        if bit(key, depth) {
+            SmtNode::Internal {
+                left: existing_hash.to_vec(),
+                right: leaf_hash,
+            }
+        } else {
+            SmtNode::Internal {
+                left: leaf_hash,
+                right: existing_hash.to_vec(),
+            }
+        }
+    };
+    store_node(store, node)
+}
+
+pub fn prove(store: &dyn SmtStore, root: &[u8], key: &[u8]) -> Result<SparseMerkleProof> {
+    if key.len() * 8 != KEY_BITS {
+        bail!("Sparse merkle keys must be 32 bytes");
+    }
+    let mut siblings: Vec<Vec<u8>> = Vec::new();
+    let mut node_hash = root.to_vec();
+    loop {
+        if node_hash == EMPTY_ROOT {
+            return Ok(SparseMerkleProof {
+                leaf: None,
+                siblings,
+            });
+        }
+        match store.get_node(&node_hash) {
+            Some(SmtNode::Leaf { key, value_hash }) => {
+                return Ok(SparseMerkleProof {
+                    leaf: Some((key, value_hash)),
+                    siblings,
+                })
+            }
+            Some(SmtNode::Internal { left, right }) => {
+                if bit(key, siblings.len()) {
+                    siblings.push(left);
+                    node_hash = right;
+                } else {
+                    siblings.push(right);
+                    node_hash = left;
+                }
+            }
+            None => bail!("Missing sparse merkle node"),
+        }
+    }
+}
+
+// keys are walked bit by bit along the siblings, checked before any bit is read
+fn verify_shape(key: &[u8], proof: &SparseMerkleProof) -> Result<()> {
+    let leaf_key_bits = proof
+        .leaf
+        .as_ref()
+        .map_or(KEY_BITS, |(leaf_key, _)| leaf_key.len() * 8);
+    if key.len() * 8 != KEY_BITS || leaf_key_bits != KEY_BITS || proof.siblings.len() > KEY_BITS {
+        bail!("Malformed sparse merkle proof");
+    }
+    Ok(())
+}
+
+fn verify_path(root: &[u8], key: &[u8], proof: &SparseMerkleProof) -> Result<()> {
+    verify_shape(key, proof)?;
+    let mut node_hash = match &proof.leaf {
+        Some((leaf_key, value_hash)) => hash_leaf(leaf_key, value_hash),
+        None => EMPTY_ROOT.to_vec(),
+    };
+    for (depth, sibling) in proof.siblings.iter().enumerate().rev() {
+        node_hash = if bit(key, depth) {
+            hash_internal(sibling, &node_hash)
+        } else {
+            hash_internal(&node_hash, sibling)
+        };
+    }
+    if node_hash != root {
+        bail!("Sparse merkle proof does not match the root");
+    }
+    Ok(())
+}
+
+// the key is present with the value
+#[allow(unused)]
+pub fn verify_inclusion(
+    root: &[u8],
+    key: &[u8],
+    value: &[u8],
+    proof: &SparseMerkleProof,
+) -> Result<()> {
+    match &proof.leaf {
+        Some((leaf_key, value_hash))
+            if leaf_key == key && value_hash[..] == Sha256::digest(value)[..] =>
+        {
+            verify_path(root, key, proof)
+        }
+        _ => bail!("Sparse merkle proof is not for this key and value"),
+    }
+}
+
+// the key is absent, its path ends in an empty subtree or in the leaf of a different
+// key that would have to be split if the key was present
+#[allow(unused)]
+pub fn verify_exclusion(root: &[u8], key: &[u8], proof: &SparseMerkleProof) -> Result<()> {
+    verify_shape(key, proof)?;
+    if let Some((leaf_key, _)) = &proof.leaf {
+        if leaf_key == key {
+            bail!("Sparse merkle proof does not exclude the key");
+        }
+        if (0..proof.siblings.len()).any(|depth| bit(leaf_key, depth) != bit(key, depth)) {
+            bail!("Sparse merkle proof ends in a leaf off the path of the key");
+        }
+    }
+    verify_path(root, key, proof)
+}
+
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore(std::collections::HashMap<Vec<u8>, SmtNode>);
+#[cfg(test)]
+impl SmtStore for MemoryStore {
+    fn get_node(&self, hash: &[u8]) -> Option<SmtNode> {
+        self.0.get(hash).cloned()
+    }
+    fn insert_node(&mut self, hash: &[u8], node: &SmtNode) {
+        self.0.insert(hash.to_vec(), node.clone());
+    }
+}
+
+#[test]
+fn test_sparse_merkle_inclusion_and_exclusion() {
+    let mut store = MemoryStore::default();
+    let keys: Vec<Vec<u8>> = (0u8..20).map(|i| Sha256::digest([i]).to_vec()).collect();
+    let mut root = EMPTY_ROOT.to_vec();
+    let empty_proof = prove(&store, &root, &keys[0]).unwrap();
+    assert!(verify_exclusion(&root, &keys[0], &empty_proof).is_ok());
+    let mut roots = vec![root.clone()];
+    for key in &keys[..10] {
+        root = insert(&mut store, &root, key, &[1]).unwrap();
+        roots.push(root.clone());
+    }
+    // inserting a present key keeps the root
+    assert_eq!(insert(&mut store, &root, &keys[0], &[2]).unwrap(), root);
+    for key in &keys[..10] {
+        let proof = prove(&store, &root, key).unwrap();
+        assert!(verify_inclusion(&root, key, &[1], &proof).is_ok());
+        assert!(verify_inclusion(&root, key, &[2], &proof).is_err());
+        assert!(verify_exclusion(&root, key, &proof).is_err());
+    }
+    for key in &keys[10..] {
+        let proof = prove(&store, &root, key).unwrap();
+        assert!(verify_exclusion(&root, key, &proof).is_ok());
+        assert!(verify_inclusion(&root, key, &[1], &proof).is_err());
+        // a proof for one key does not exclude another
+        let other_proof = prove(&store, &root, &keys[0]).unwrap();
+        assert!(verify_exclusion(&root, key, &other_proof).is_err());
+    }
+    // past roots remain provable
+    let proof = prove(&store, &roots[5], &keys[7]).unwrap();
+    assert!(verify_exclusion(&roots[5], &keys[7], &proof).is_ok());
+}
+
+#[test]
+fn test_sparse_merkle_malformed_proofs() {
+    let mut store = MemoryStore::default();
+    let keys: Vec<Vec<u8>> = (0u8..2).map(|i| Sha256::digest([i]).to_vec()).collect();
+    let root = insert(&mut store, &EMPTY_ROOT, &keys[0], &[1]).unwrap();
+    let proof = prove(&store, &root, &keys[1]).unwrap();
+    assert!(verify_exclusion(&root, &keys[1], &proof).is_ok());
+    // more siblings than key bits
+    let mut long_proof = proof.clone();
+    long_proof.siblings = vec![EMPTY_ROOT.to_vec(); KEY_BITS + 1];
+    assert!(verify_exclusion(&root, &keys[1], &long_proof).is_err());
+    assert!(verify_inclusion(&root, &keys[0], &[1], &long_proof).is_err());
+    // short keys are rejected instead of being read past their end
+    let mut short_leaf_proof = proof.clone();
+    short_leaf_proof.leaf = Some((vec![0], Sha256::digest([1]).to_vec()));
+    short_leaf_proof.siblings = vec![EMPTY_ROOT.to_vec(); 16];
+    assert!(verify_exclusion(&root, &keys[1], &short_leaf_proof).is_err());
+    assert!(verify_exclusion(&root, &[0], &short_leaf_proof).is_err());
+    assert!(verify_exclusion(&root, &[], &proof).is_err());
+    assert!(verify_inclusion(&root, &[0], &[1], &short_leaf_proof).is_err());
+}
@@ -1,4 +1,4 @@
-use crate::types::ConsensusCommitment;
+use crate::types::{ConsensusCommitment, FinalizedBlock, TimeoutVote};
 use crate::{consensus::logic::current_round, types::Block};
 use colored::Colorize;
 use reqwest::{Client, Response};
@@ -10,21 +10,49 @@ pub struct Gossipper {
     pub client: Client,
 }
 pub async fn send_proposal(client: Client, peer: Peer, json_block: String) -> Option<Response> {
-    let response: Option<Response> = match client
-        .post(format!("http://{}{}", &peer, "/propose"))
+    let response: Option<Response> = client
+        .post(format!("http://{}{}", peer, "/propose"))
         .header("Content-Type", "application/json")
         .body(json_block)
         .send()
         .await
-    {
-        Ok(r) => Some(r),
-        Err(_) => None,
-    };
+        .ok();
     response
 }
+// the next height of the peer, None if the peer is unreachable
+pub async fn fetch_height(client: Client, peer: Peer) -> Option<u32> {
+    let response = client
+        .get(format!("http://{}{}", peer, "/get/height"))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .ok()?;
+    serde_json::from_str(&response.text().await.ok()?).ok()
+}
+pub async fn fetch_block_range(
+    client: Client,
+    peer: Peer,
+    from: u32,
+    to: u32,
+) -> anyhow::Result<Vec<FinalizedBlock>> {
+    let response = client
+        .get(format!(
+            "http://{}/get/blocks?from={}&to={}",
+            peer, from, to
+        ))
+        .timeout(Duration::from_secs(60))
+        .send()
+        .await?;
+    Ok(serde_json::from_str(&response.text().await?)?)
+}
 
 impl Gossipper {
-    pub async fn gossip_pending_block(&self, block: Block, last_block_unix_timestamp: u32) {
+    pub async fn gossip_pending_block(
+        &self,
+        block: Block,
+        last_block_unix_timestamp: u32,
+        round_duration: u32,
+    ) {
         for peer in self.peers.clone() {
             let client_clone = self.client.clone();
             let peer_clone = peer;
@@ -34,8 +62,8 @@ impl Gossipper {
                 continue;
             };
             tokio::spawn(async move {
-                let start_round = current_round(last_block_unix_timestamp);
-                let round = current_round(last_block_unix_timestamp);
+                let start_round = current_round(last_block_unix_timestamp, round_duration);
+                let round = current_round(last_block_unix_timestamp, round_duration);
                 if start_round < round {
                     println!("[Warning] Gossipping old Block");
                 }
@@ -83,7 +111,7 @@ impl Gossipper {
             };
 
             match client_clone
-                .post(format!("http://{}{}", &peer, "/commit"))
+                .post(format!("http://{}{}", peer, "/commit"))
                 .header("Content-Type", "application/json")
                 .body(json_commitment_clone)
                 .timeout(Duration::from_secs(30))
@@ -113,16 +141,43 @@ impl Gossipper {
             }
         }
     }
+
+    pub async fn gossip_timeout_vote(&self, vote: TimeoutVote) {
+        let json_vote: String = serde_json::to_string(&vote).unwrap();
+        for peer in self.peers.clone() {
+            let this_node = env::var("API_HOST_WITH_PORT").unwrap_or("0.0.0.0:8080".to_string());
+            if docker_skip_self(&this_node, peer) {
+                continue;
+            };
+            match self
+                .client
+                .post(format!("http://{}{}", peer, "/timeout"))
+                .header("Content-Type", "application/json")
+                .body(json_vote.clone())
+                .timeout(Duration::from_secs(30))
+                .send()
+                .await
+            {
+                Ok(_) => {}
+                Err(e) => println!(
+                    "{}",
+                    format_args!(
+                        "{} Failed to send Timeout Vote to peer: {}, reason: {}",
+                        "[Warning]".yellow(),
+                        &peer,
+                        e
+                    )
+                ),
+            }
+        }
+    }
 }
 pub fn docker_skip_self(this_node: &str, peer: &str) -> bool {
-    if this_node == "0.0.0.0:8080" && peer == "rust-node-1:8080" {
-        return true;
-    } else if this_node == "0.0.0.0:8081" && peer == "rust-node-2:8081" {
-        return true;
-    } else if this_node == "0.0.0.0:8082" && peer == "rust-node-3:8082" {
-        return true;
-    } else if this_node == "0.0.0.0:8083" && peer == "rust-node-4:8083" {
-        return true;
-    }
-    false
+    matches!(
+        (this_node, peer),
+        ("0.0.0.0:8080", "rust-node-1:8080")
+            | ("0.0.0.0:8081", "rust-node-2:8081")
+            | ("0.0.0.0:8082", "rust-node-3:8082")
+            | ("0.0.0.0:8083", "rust-node-4:8083")
+    )
 }
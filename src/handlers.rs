@@ -1,77 +1,236 @@
 use tokio::sync::Mutex;
 extern crate alloc;
-use crate::config::consensus::CONSENSUS_THRESHOLD;
-use crate::config::network::PEERS;
+use crate::config::{genesis::Genesis, network::PEERS};
+use crate::consensus::logic::{
+    block_round, current_round, verify_block_header, verify_block_messages, verify_finalized_block,
+    verify_timeout_certificate, verify_timeout_vote,
+};
 use crate::gossipper::Gossipper;
-use crate::state::server::BlockStore;
 use crate::state::server::InMemoryConsensus;
 use crate::state::server::SqLiteBlockStore;
-use crate::types::BlockCommitment;
+use crate::state::server::{BlockStore, EvidenceStore};
+use crate::state::server::{
+    SignatureKind, SqLiteEvidenceStore, SqLiteMessageIndex, SqLiteSigningHistory,
+};
 use crate::types::GenericSignature;
-use crate::{crypto::ecdsa::deserialize_vk, types::Block};
+use crate::types::Message;
+use crate::types::{BlockCommitment, BlockHeader};
+use crate::types::{FinalizedBlock, QuorumCertificate, TimeoutCertificate, TimeoutVote};
+use crate::{
+    crypto::{ecdsa::deserialize_vk, signer::SignRequest},
+    types::Block,
+};
 use crate::{get_current_time, ServerState};
 use alloc::sync::Arc;
+use anyhow::{anyhow, bail, Result};
 use ciphercurve_trie::{
     insert_leaf,
-    store::types::{Hashable, Leaf, Node},
+    store::{
+        db::sql::TrieDB as MerkleTrieDB,
+        types::{Node, Root},
+    },
 };
 use colored::Colorize;
-use k256::ecdsa::signature::{Signer, Verifier};
-use k256::ecdsa::Signature;
-use reqwest::Response;
+use k256::ecdsa::signature::Verifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+
+pub fn insert_messages_into_trie(
+    trie_state: &mut MerkleTrieDB,
+    trie_root: Root,
+    height: u32,
+    messages: &[Message],
+) -> Root {
+    let mut root_node = Node::Root(trie_root);
+    for (index, message) in messages.iter().enumerate() {
+        let mut leaf = message.trie_leaf(height, index as u32);
+        let new_root = insert_leaf(trie_state, &mut leaf, root_node)
+            .expect("[Critical] Failed to insert message into the trie");
+        root_node = Node::Root(new_root);
+    }
+    root_node
+        .unwrap_as_root()
+        .expect("[Critical] Failed to unwrap as root, this should never happen :(")
+}
+
+// only creates a genesis Block for a fresh database, a restarted node keeps its history
+pub fn open_block_store(block_state: &mut BlockStore, genesis: &Genesis) {
+    if !block_state.block_exists(0) {
+        block_state.trigger_genesis(genesis.genesis_timestamp, genesis.validator_set().id());
+    } else if block_state.get_block_by_height(0).header.timestamp != genesis.genesis_timestamp {
+        panic!(
+            "[Error] Existing BlockStore does not belong to chain: {}",
+            genesis.chain_id
+        );
+    } else {
+        println!(
+            "{}",
+            format_args!(
+                "{} Found existing BlockStore, resuming from Block: {}",
+                "[Info]".green(),
+                block_state.current_block_height() - 1
+            )
+        );
+    }
+}
 
-pub async fn handle_synchronization_response(
+// the trie root is persisted after each block, but a node may crash between storing
+// a block and its root. Replay the messages of any such blocks so that the trie
+// matches the stored tip again.
+pub fn restore_trie_root(block_state: &mut BlockStore, trie_state: &mut MerkleTrieDB) -> Root {
+    let tip = block_state.current_block_height() - 1;
+    let (mut height, mut trie_root) = block_state
+        .latest_state_root()
+        .unwrap_or((0, Root::empty()));
+    while height < tip {
+        height += 1;
+        let block = block_state.get_block_by_height(height);
+        trie_root = insert_messages_into_trie(trie_state, trie_root, height, &block.messages);
+        block_state.insert_state_root(height, trie_root.clone());
+        println!(
+            "{}",
+            format_args!("{} Replayed Block into Trie: {}", "[Info]".green(), height)
+        );
+    }
+    trie_root
+}
+
+// the trie root after inserting the messages of the next height on top of the current root.
+// A proposal is evaluated when it is built, signed and stored, its messages are only inserted once
+pub fn post_state_root(shared_state: &mut ServerState, height: u32, messages: &[Message]) -> Root {
+    let messages_root = Block::messages_root(messages);
+    if let Some(root) = shared_state.pending_state_roots.get(&messages_root) {
+        return root.clone();
+    }
+    let trie_root = shared_state.merkle_trie_root.clone();
+    let root = insert_messages_into_trie(
+        &mut shared_state.merkle_trie_state,
+        trie_root,
+        height,
+        messages,
+    );
+    shared_state
+        .pending_state_roots
+        .insert(messages_root, root.clone());
+    root
+}
+
+// moves the trie to the root of a stored Block, pending roots were computed on the old one
+pub fn commit_state_root(shared_state: &mut ServerState, root: Root) {
+    shared_state.merkle_trie_root = root;
+    shared_state.pending_state_roots.clear();
+}
+
+// stores Blocks served by a peer in order, each is verified against the local tip, the
+// validator set and the quorum first. Returns the number of stored Blocks, an error means
+// the peer served provably invalid data, the Blocks before the invalid one are kept.
+// A first Block that doesn't extend the local tip stops without an error, the peer or
+// this node might be on another fork
+pub async fn apply_synchronized_blocks(
     shared_state: Arc<Mutex<ServerState>>,
     block_state: Arc<Mutex<BlockStore>>,
     consensus_state: Arc<Mutex<InMemoryConsensus>>,
-    response: Response,
-    next_height: u32,
-) {
-    println!("[Info] Querying Block: {}", &next_height);
-    let block_serialized = response.text().await.unwrap();
-    if block_serialized != "[Warning] Requested Block that does not exist" {
-        let block: Block = serde_json::from_str(&block_serialized).unwrap();
-        let mut block_state_lock = block_state.lock().await;
-        block_state_lock.insert_block(next_height, block.clone());
-        // insert messages into the trie
-        let mut shared_state_lock = shared_state.lock().await;
-        let mut root_node = Node::Root(shared_state_lock.merkle_trie_root.clone());
-        let messages = &block.messages;
-        for message in messages {
-            let mut leaf = Leaf::new(Vec::new(), Some(message.data.clone()));
-            leaf.hash();
-            leaf.key = leaf
-                .hash
-                .clone()
-                .unwrap()
-                .iter()
-                .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1))
-                .collect();
-            leaf.hash();
-            let new_root = insert_leaf(
-                &mut shared_state_lock.merkle_trie_state,
-                &mut leaf,
-                root_node,
-            )
-            .expect("[Critical] Failed to insert leaf!");
-            root_node = Node::Root(new_root);
+    finalized_blocks: Vec<FinalizedBlock>,
+) -> Result<u32> {
+    let mut block_state_lock = block_state.lock().await;
+    let mut consensus_state_lock = consensus_state.lock().await;
+    let mut shared_state_lock = shared_state.lock().await;
+    let mut applied: u32 = 0;
+    for finalized_block in finalized_blocks {
+        let next_height = block_state_lock.current_block_height();
+        // Blocks might have been stored through consensus in the meantime
+        if finalized_block.block.header.height < next_height {
+            continue;
+        }
+        let parent = block_state_lock.get_block_by_height(next_height - 1);
+        let header = &finalized_block.block.header;
+        if applied == 0 && (header.height != next_height || header.parent_hash != parent.hash()) {
+            println!(
+                "{}",
+                format_args!(
+                    "{} Synchronized Block {} does not extend the local tip: {}",
+                    "[Warning]".yellow(),
+                    header.height,
+                    next_height - 1
+                )
+            );
+            break;
         }
+        verify_finalized_block(
+            &finalized_block,
+            &parent,
+            &consensus_state_lock.validators,
+            &consensus_state_lock.genesis.quorum,
+        )?;
+        verify_block_messages(
+            &finalized_block.block.messages,
+            &consensus_state_lock.genesis.chain_id,
+            |sender| block_state_lock.get_finalized_nonce(sender),
+        )?;
+        let block = finalized_block.block;
+        let new_root = post_state_root(&mut shared_state_lock, next_height, &block.messages);
+        if new_root.hash != block.header.state_root {
+            bail!("Block state root does not match the messages");
+        }
+        let message_index_root = shared_state_lock.message_index.insert_messages(
+            &parent.header.message_index_root,
+            next_height,
+            &block.messages,
+        );
+        if message_index_root != block.header.message_index_root {
+            bail!("Block message index root does not match the messages");
+        }
+        block_state_lock.insert_block(next_height, block, finalized_block.quorum_certificate);
         // update trie root
-        shared_state_lock.merkle_trie_root = root_node
-            .unwrap_as_root()
-            .expect("[Critical] Failed to unwrap as root, this should never happen :(");
-        let mut consensus_state_lock = consensus_state.lock().await;
+        block_state_lock.insert_state_root(next_height, new_root.clone());
+        commit_state_root(&mut shared_state_lock, new_root);
         consensus_state_lock.reinitialize();
+        applied += 1;
+    }
+    if applied > 0 {
         println!(
             "{}",
-            format_args!("{} Synchronized Block: {}", "[Info]".green(), next_height)
+            format_args!(
+                "{} Synchronized Blocks up to: {}, New Trie Root: {:?}",
+                "[Info]".green(),
+                block_state_lock.current_block_height() - 1,
+                shared_state_lock.merkle_trie_root.hash
+            )
         );
+    }
+    Ok(applied)
+}
+
+fn record_signature(
+    evidence: &mut EvidenceStore,
+    header: &BlockHeader,
+    round: u32,
+    kind: SignatureKind,
+    validator: &[u8],
+    payload: &[u8],
+    signature: &[u8],
+) {
+    if evidence
+        .record_signature(
+            header.height,
+            round,
+            header.view,
+            kind,
+            validator,
+            payload,
+            signature,
+        )
+        .is_some()
+    {
         println!(
             "{}",
             format_args!(
-                "{} New Trie Root: {:?}",
-                "[Info]".green(),
-                shared_state_lock.merkle_trie_root.hash
+                "{} Validator {:?} equivocated, conflicting {:?} at height: {}, round: {}, view: {}",
+                "[Warning]".yellow(),
+                validator,
+                kind,
+                header.height,
+                round,
+                header.view
             )
         );
     }
@@ -92,19 +251,18 @@ pub async fn handle_block_proposal(
     let mut consensus_state_lock = consensus_state.lock().await;
     let mut shared_state_lock = shared_state.lock().await;
 
+    let block_hash = proposal.hash();
     let early_revert: bool = match &consensus_state_lock.lowest_block {
         Some(v) => {
-            if proposal.to_bytes() < v.clone() {
-                consensus_state_lock.lowest_block = Some(proposal.to_bytes());
-                false
-            } else if proposal.to_bytes() == v.clone() {
+            if proposal.header.to_bytes() < v.clone() {
+                consensus_state_lock.lowest_block = Some(proposal.header.to_bytes());
                 false
             } else {
-                true
+                proposal.header.to_bytes() != v.clone()
             }
         }
         None => {
-            consensus_state_lock.lowest_block = Some(proposal.to_bytes());
+            consensus_state_lock.lowest_block = Some(proposal.header.to_bytes());
             false
         }
     };
@@ -114,16 +272,23 @@ pub async fn handle_block_proposal(
     }
     // sign the block if it has not been signed yet
     let mut is_signed = false;
-    let block_commitments = proposal.commitments.clone().unwrap_or(Vec::new());
-    let mut commitment_count: u32 = 0;
+    let block_commitments = proposal.commitments.clone().unwrap_or_default();
+    // each validator contributes its voting power at most once
+    let mut voted: Vec<VerifyingKey> = Vec::new();
+    let mut certificate_signatures: Vec<BlockCommitment> = Vec::new();
     for commitment in block_commitments {
         let commitment_vk = deserialize_vk(&commitment.validator);
         if consensus_state_lock.validators.contains(&commitment_vk) {
             match commitment_vk.verify(
-                &proposal.to_bytes(),
+                &block_hash,
                 &Signature::from_slice(&commitment.signature).unwrap(),
             ) {
-                Ok(_) => commitment_count += 1,
+                Ok(_) => {
+                    if !voted.contains(&commitment_vk) {
+                        voted.push(commitment_vk);
+                        certificate_signatures.push(commitment.clone());
+                    }
+                }
                 Err(_) => {
                     println!(
                         "{}",
@@ -146,49 +311,116 @@ pub async fn handle_block_proposal(
             is_signed = true;
         }
     }
+    let collected_voting_power: u64 = voted
+        .iter()
+        .map(|validator| consensus_state_lock.validators.stake_of(validator))
+        .sum();
+    let required_voting_power = consensus_state_lock.required_voting_power();
     println!(
-        "[Info] Commitment count for proposal: {}",
-        &commitment_count
+        "[Info] Voting power for proposal: {}, required: {}, total: {}",
+        collected_voting_power,
+        required_voting_power,
+        consensus_state_lock.total_voting_power()
     );
 
     let previous_block_height = block_state_lock.current_block_height() - 1;
-    if proposal.height != previous_block_height + 1 {
+    if proposal.header.height != previous_block_height + 1 {
         return Some(error_response);
     }
-    if commitment_count >= CONSENSUS_THRESHOLD {
+    let parent = block_state_lock.get_block_by_height(previous_block_height);
+    if let Err(e) = verify_block_header(proposal, &parent, &consensus_state_lock.validators) {
         println!(
             "{}",
-            format_args!("{} Received Valid Block", "[Info]".green())
+            format_args!("{} Invalid Block header: {}", "[Warning]".yellow(), e)
+        );
+        return Some(error_response);
+    }
+    if let Err(e) = verify_block_messages(
+        &proposal.messages,
+        &consensus_state_lock.genesis.chain_id,
+        |sender| block_state_lock.get_finalized_nonce(sender),
+    ) {
+        println!(
+            "{}",
+            format_args!("{} Invalid Block messages: {}", "[Warning]".yellow(), e)
+        );
+        return Some(error_response);
+    }
+    // every verified signature on a proposal for the next height is remembered, so that
+    // a validator signing a different block for the same height, round and view is detected.
+    // The round and view are the ones the proposal was signed for, not the ones of this node
+    let last_block_unix_timestamp = parent.header.timestamp;
+    let round_duration = consensus_state_lock.genesis.round_duration;
+    let proposal_round = block_round(
+        proposal.header.timestamp,
+        last_block_unix_timestamp,
+        round_duration,
+    );
+    if let Some(signature) = proposal.signature.as_ref() {
+        record_signature(
+            &mut consensus_state_lock.evidence,
+            &proposal.header,
+            proposal_round,
+            SignatureKind::Proposal,
+            &proposal.header.proposer,
+            &block_hash,
+            signature,
+        );
+    }
+    for commitment in &certificate_signatures {
+        record_signature(
+            &mut consensus_state_lock.evidence,
+            &proposal.header,
+            proposal_round,
+            SignatureKind::Commitment,
+            &commitment.validator,
+            &block_hash,
+            &commitment.signature,
         );
-        block_state_lock.insert_block(proposal.height, proposal.clone());
-        // insert messages into the trie
-        let mut root_node = Node::Root(shared_state_lock.merkle_trie_root.clone());
-        for message in &proposal.messages {
-            let mut leaf = Leaf::new(Vec::new(), Some(message.data.clone()));
-            leaf.hash();
-            leaf.key = leaf
-                .hash
-                .clone()
-                .unwrap()
-                .iter()
-                .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1))
-                .collect();
-            leaf.hash();
-            let new_root = insert_leaf(
-                &mut shared_state_lock.merkle_trie_state,
-                &mut leaf,
-                root_node,
+    }
+    let new_root = post_state_root(
+        &mut shared_state_lock,
+        proposal.header.height,
+        &proposal.messages,
+    );
+    let message_index_root = shared_state_lock.message_index.insert_messages(
+        &parent.header.message_index_root,
+        proposal.header.height,
+        &proposal.messages,
+    );
+    if new_root.hash != proposal.header.state_root
+        || message_index_root != proposal.header.message_index_root
+    {
+        println!(
+            "{}",
+            format_args!(
+                "{} Block state roots do not match the messages",
+                "[Warning]".yellow()
             )
-            .expect("Duplicate insert? This is currently a breaking bug!");
-            root_node = Node::Root(new_root);
-        }
-        // update in-memory trie root
-        shared_state_lock.merkle_trie_root = root_node
-            .unwrap_as_root()
-            .expect("failed to unwrap as root, this should never happen :(");
+        );
+        return Some(error_response);
+    }
+    if collected_voting_power >= required_voting_power {
+        println!(
+            "{}",
+            format_args!("{} Received Valid Block", "[Info]".green())
+        );
+        let certificate = QuorumCertificate {
+            block_hash: proposal.hash(),
+            validator_set_id: consensus_state_lock.validators.id(),
+            signatures: certificate_signatures,
+        };
+        block_state_lock.insert_block(proposal.header.height, proposal.clone(), certificate);
+        // persist the root before updating the in-memory trie root
+        block_state_lock.insert_state_root(proposal.header.height, new_root.clone());
+        commit_state_root(&mut shared_state_lock, new_root);
         println!(
             "{}",
-            format_args!("{} Block was stored: {}", "[Info]".green(), proposal.height)
+            format_args!(
+                "{} Block was stored: {}",
+                "[Info]".green(),
+                proposal.header.height
+            )
         );
         println!(
             "{}",
@@ -198,19 +430,64 @@ pub async fn handle_block_proposal(
                 shared_state_lock.merkle_trie_root.hash
             )
         );
-    } else if !is_signed && (previous_block_height + 1 == proposal.height) {
-        let local_sk = consensus_state_lock.local_signing_key.clone();
-        let block_bytes = proposal.to_bytes();
-        let signature: Signature = local_sk.sign(&block_bytes);
+    } else if !is_signed {
+        // only Blocks proposed in the round and view this node is in are signed
+        if proposal_round != current_round(last_block_unix_timestamp, round_duration)
+            || proposal.header.view != consensus_state_lock.view
+        {
+            println!(
+                "{}",
+                format_args!(
+                    "{} Refusing to sign Block from another round or view at height: {}",
+                    "[Warning]".yellow(),
+                    proposal.header.height
+                )
+            );
+            return Some(error_response);
+        }
+        // record before signing, a crash must never allow a second signature for this height
+        if !consensus_state_lock.signing_history.check_and_record(
+            proposal.header.height,
+            SignatureKind::Commitment,
+            &block_hash,
+        ) {
+            println!(
+                "{}",
+                format_args!(
+                    "{} Refusing to sign conflicting Block at height: {}",
+                    "[Warning]".yellow(),
+                    proposal.header.height
+                )
+            );
+            return Some(error_response);
+        }
+        // the signer might be remote, no lock is held while waiting for it
+        let signer = Arc::clone(&consensus_state_lock.signer);
+        let local_validator = consensus_state_lock
+            .local_validator
+            .to_sec1_bytes()
+            .to_vec();
+        drop(shared_state_lock);
+        drop(consensus_state_lock);
+        drop(block_state_lock);
+        let signature: Signature = match signer
+            .sign(&SignRequest::Header(proposal.header.clone()))
+            .await
+        {
+            Ok(signature) => signature,
+            Err(e) => {
+                println!(
+                    "{}",
+                    format_args!("{} Failed to sign Block: {}", "[Error]".red(), e)
+                );
+                return Some(error_response);
+            }
+        };
         let signature_serialized: GenericSignature = signature.to_bytes().to_vec();
         let unix_timestamp = get_current_time();
         let commitment = BlockCommitment {
             signature: signature_serialized,
-            validator: consensus_state_lock
-                .local_validator
-                .to_sec1_bytes()
-                .to_vec()
-                .clone(),
+            validator: local_validator,
             timestamp: unix_timestamp,
         };
         match proposal.commitments.as_mut() {
@@ -218,9 +495,6 @@ pub async fn handle_block_proposal(
             None => proposal.commitments = Some(vec![commitment]),
         }
         println!("[Info] Signed Block is being gossipped");
-        let last_block_unix_timestamp = block_state_lock
-            .get_block_by_height(previous_block_height)
-            .timestamp;
 
         // todo: spawn a task for this
         let gossipper = Gossipper {
@@ -229,21 +503,180 @@ pub async fn handle_block_proposal(
         };
 
         let proposal = proposal.clone();
-        drop(shared_state_lock);
-        drop(block_state_lock);
-        drop(consensus_state_lock);
         // awaits the responses from all peers before progressing
         let _ = gossipper
-            .gossip_pending_block(proposal, last_block_unix_timestamp)
+            .gossip_pending_block(proposal, last_block_unix_timestamp, round_duration)
             .await;
     } else {
         println!(
             "{}",
             format_args!(
-                "{} Block is signed but lacks commitments",
-                "[Warning]".yellow()
+                "{} Block is signed but lacks commitments, voting power: {}/{}",
+                "[Warning]".yellow(),
+                collected_voting_power,
+                required_voting_power
             )
         );
     }
     None
 }
+
+// counts a timeout vote for the next view and hands the round to the fallback proposer
+// once the votes reach the quorum, the votes are stored as evidence of the view change
+pub fn handle_timeout_vote(
+    consensus_state_lock: &mut InMemoryConsensus,
+    mut vote: TimeoutVote,
+    next_height: u32,
+    round: u32,
+) -> Result<()> {
+    // a vote for a later view carries the certificate of the view before it, a node that
+    // missed view changes adopts that view before counting the vote
+    if vote.view > consensus_state_lock.view + 1 {
+        let certificate = vote.certificate.take().ok_or_else(|| {
+            anyhow!(
+                "Timeout vote for view {} lacks a certificate, current view is {}",
+                vote.view,
+                consensus_state_lock.view
+            )
+        })?;
+        if certificate.view + 1 != vote.view {
+            bail!(
+                "Timeout vote for view {} carries a certificate for view {}",
+                vote.view,
+                certificate.view
+            );
+        }
+        verify_timeout_certificate(
+            &certificate,
+            &consensus_state_lock.validators,
+            &consensus_state_lock.genesis.quorum,
+            &consensus_state_lock.genesis.chain_id,
+            next_height,
+            round,
+        )?;
+        consensus_state_lock
+            .evidence
+            .insert_timeout_certificate(&certificate);
+        consensus_state_lock.advance_view(round, certificate);
+        println!(
+            "{}",
+            format_args!(
+                "{} Caught up to view: {}, fallback proposer: {:?}",
+                "[Info]".green(),
+                consensus_state_lock.view,
+                consensus_state_lock.round_winner
+            )
+        );
+    }
+    // certificates are not nested into the certificate of the next view
+    vote.certificate = None;
+    verify_timeout_vote(
+        &vote,
+        &consensus_state_lock.validators,
+        &consensus_state_lock.genesis.chain_id,
+        next_height,
+        round,
+        consensus_state_lock.view,
+    )?;
+    if consensus_state_lock
+        .timeout_votes
+        .iter()
+        .any(|timeout_vote| timeout_vote.validator == vote.validator)
+    {
+        return Ok(());
+    }
+    consensus_state_lock.timeout_votes.push(vote);
+    let collected_voting_power: u64 = consensus_state_lock
+        .timeout_votes
+        .iter()
+        .map(|timeout_vote| {
+            consensus_state_lock
+                .validators
+                .stake_of(&deserialize_vk(&timeout_vote.validator))
+        })
+        .sum();
+    if collected_voting_power < consensus_state_lock.required_voting_power() {
+        return Ok(());
+    }
+    let certificate = TimeoutCertificate {
+        height: next_height,
+        round,
+        view: consensus_state_lock.view + 1,
+        votes: consensus_state_lock.timeout_votes.clone(),
+    };
+    consensus_state_lock
+        .evidence
+        .insert_timeout_certificate(&certificate);
+    consensus_state_lock.advance_view(round, certificate);
+    println!(
+        "{}",
+        format_args!(
+            "{} View change to view: {}, fallback proposer: {:?}",
+            "[Info]".green(),
+            consensus_state_lock.view,
+            consensus_state_lock.round_winner
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_restart_resumes_history() {
+    use crate::test_utils::TempDb;
+    let db = TempDb::new("restart");
+    let genesis = Genesis::load("genesis.json");
+    let open = || {
+        let mut block_state = BlockStore {
+            db_path: db.path.clone(),
+        };
+        block_state.setup();
+        open_block_store(&mut block_state, &genesis);
+        let mut trie_state = MerkleTrieDB {
+            path: db.path.clone(),
+            cache: None,
+        };
+        trie_state.setup();
+        let trie_root = restore_trie_root(&mut block_state, &mut trie_state);
+        (block_state, trie_state, trie_root)
+    };
+    let message = |data: u8| Message {
+        data: vec![data],
+        timestamp: 0,
+        envelope: None,
+    };
+    let block = |height: u32, messages: Vec<Message>| {
+        let mut block = Block::genesis(genesis.genesis_timestamp);
+        block.header.height = height;
+        block.messages = messages;
+        block
+    };
+    let certificate = || QuorumCertificate {
+        block_hash: vec![],
+        validator_set_id: vec![],
+        signatures: vec![],
+    };
+    let (mut block_state, mut trie_state, trie_root) = open();
+    let genesis_block = block_state.get_block_by_height(0);
+    assert_eq!(block_state.current_block_height(), 1);
+    assert_eq!(trie_root.hash, Root::empty().hash);
+    let first_root = insert_messages_into_trie(&mut trie_state, trie_root, 1, &[message(1)]);
+    block_state.insert_block(1, block(1, vec![message(1)]), certificate());
+    block_state.insert_state_root(1, first_root.clone());
+    // the node stops after storing the second Block but before persisting its trie root
+    block_state.insert_block(2, block(2, vec![message(2)]), certificate());
+    drop((block_state, trie_state));
+    let (block_state, _, trie_root) = open();
+    // the genesis Block is kept instead of being created again
+    assert_eq!(block_state.current_block_height(), 3);
+    assert_eq!(
+        block_state.get_block_by_height(0).hash(),
+        genesis_block.hash()
+    );
+    assert_eq!(block_state.get_state_root(1).unwrap().hash, first_root.hash);
+    // the missing root is replayed on top of the persisted one and stored
+    assert_ne!(trie_root.hash, first_root.hash);
+    assert_eq!(block_state.get_state_root(2).unwrap().hash, trie_root.hash);
+    drop(block_state);
+    let (_, _, resumed_root) = open();
+    assert_eq!(resumed_root.hash, trie_root.hash);
+}
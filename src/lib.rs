@@ -8,6 +8,9 @@ pub mod config;
 pub mod consensus;
 pub mod crypto;
 pub mod gossipper;
+pub mod light_client;
+#[cfg(test)]
+mod test_utils;
 pub mod types;
 
 pub fn get_current_time() -> u32 {
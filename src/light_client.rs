@@ -0,0 +1,425 @@
+use crate::crypto::sparse_merkle::{self, SparseMerkleProof};
+use crate::{
+    config::{genesis::Genesis, network::MAX_BLOCK_RANGE},
+    consensus::{logic::verify_quorum_certificate, quorum::QuorumRule, validators::ValidatorSet},
+    types::{Block, BlockHeader, FinalizedHeader, Message},
+};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use ciphercurve_trie::{
+    merkle::{verify_merkle_proof, MerkleProof},
+    store::types::Node,
+};
+use std::time::Duration;
+
+// where the light client fetches finalized headers from, nothing it serves is trusted
+#[async_trait]
+pub trait HeaderSource {
+    // the headers from `from` to `to`, both inclusive, fewer if the source is behind
+    async fn fetch_headers(&self, from: u32, to: u32) -> Result<Vec<FinalizedHeader>>;
+}
+// a node serving `/get/headers`, e.g. "rust-node-1:8080"
+pub struct HttpHeaderSource {
+    pub client: reqwest::Client,
+    pub peer: String,
+}
+#[async_trait]
+impl HeaderSource for HttpHeaderSource {
+    async fn fetch_headers(&self, from: u32, to: u32) -> Result<Vec<FinalizedHeader>> {
+        let response = self
+            .client
+            .get(format!(
+                "http://{}/get/headers?from={}&to={}",
+                self.peer, from, to
+            ))
+            .timeout(Duration::from_secs(60))
+            .send()
+            .await?;
+        Ok(serde_json::from_str(&response.text().await?)?)
+    }
+}
+
+// follows the header chain of a network from a trusted header, every following header must
+// link to its parent and carry a quorum certificate of the validator set. Nothing served by
+// a node is trusted before it was verified against the validator set
+pub struct LightClient {
+    validators: ValidatorSet,
+    quorum: QuorumRule,
+    // verified headers, consecutive from the trusted header
+    headers: Vec<BlockHeader>,
+}
+impl LightClient {
+    // trusts the genesis Block derived from the genesis file
+    pub fn from_genesis(genesis: &Genesis) -> Self {
+        Self::from_checkpoint(
+            Block::genesis(genesis.genesis_timestamp).header,
+            genesis.validator_set(),
+            genesis.quorum,
+        )
+    }
+    // trusts a header that was obtained out of band
+    pub fn from_checkpoint(
+        header: BlockHeader,
+        validators: ValidatorSet,
+        quorum: QuorumRule,
+    ) -> Self {
+        Self {
+            validators,
+            quorum,
+            headers: vec![header],
+        }
+    }
+    pub fn latest_header(&self) -> &BlockHeader {
+        self.headers.last().unwrap()
+    }
+    pub fn header(&self, height: u32) -> Option<&BlockHeader> {
+        let index = height.checked_sub(self.headers[0].height)?;
+        self.headers.get(index as usize)
+    }
+    // verifies the header following the latest header and appends it
+    pub fn update(&mut self, finalized_header: &FinalizedHeader) -> Result<()> {
+        let parent = self.latest_header();
+        let header = &finalized_header.header;
+        if header.height != parent.height + 1 {
+            bail!(
+                "Header has height {}, expected {}",
+                header.height,
+                parent.height + 1
+            );
+        }
+        if header.timestamp < parent.timestamp {
+            bail!("Header timestamp is before its parent");
+        }
+        if header.parent_hash != parent.hash() {
+            bail!("Header does not link to its parent");
+        }
+        verify_quorum_certificate(
+            header,
+            &finalized_header.quorum_certificate,
+            &self.validators,
+            &self.quorum,
+        )?;
+        self.headers.push(header.clone());
+        Ok(())
+    }
+    // applies headers in order, the headers before an invalid one are kept
+    pub fn update_all(&mut self, finalized_headers: &[FinalizedHeader]) -> Result<()> {
+        for finalized_header in finalized_headers {
+            // headers up to the latest one might be served again
+            if finalized_header.header.height <= self.latest_header().height {
+                continue;
+            }
+            self.update(finalized_header)?;
+        }
+        Ok(())
+    }
+    // fetches and verifies the headers after the latest verified header until the source
+    // has no more, returns the height of the latest verified header
+    pub async fn sync(&mut self, source: &dyn HeaderSource) -> Result<u32> {
+        loop {
+            let from = self.latest_header().height + 1;
+            let finalized_headers = source
+                .fetch_headers(from, from + MAX_BLOCK_RANGE - 1)
+                .await?;
+            self.update_all(&finalized_headers)?;
+            // a source that is behind or repeats itself has nothing more to offer
+            if self.latest_header().height < from {
+                return Ok(self.latest_header().height);
+            }
+        }
+    }
+    // verifies a merkle proof for a message at its position against the state root of a
+    // verified header at the same or a later height
+    pub fn verify_message_inclusion(
+        &self,
+        height: u32,
+        message: &Message,
+        position: (u32, u32),
+        proof: &MerkleProof,
+    ) -> Result<()> {
+        let state_root = self
+            .header(height)
+            .ok_or_else(|| anyhow!("No verified header at height {}", height))?
+            .state_root
+            .clone()
+            .ok_or_else(|| anyhow!("The trie is empty at height {}", height))?;
+        let (message_height, index) = position;
+        if message_height > height {
+            bail!("Message position is after the header");
+        }
+        // the proof hashes from its first node up to the root, which must be the leaf
+        let leaf = message.trie_leaf(message_height, index);
+        match proof.nodes.first() {
+            Some((_, Node::Leaf(proven))) if proven.key == leaf.key && proven.hash == leaf.hash => {
+            }
+            _ => bail!("Merkle proof is for a different message"),
+        }
+        verify_merkle_proof(proof.nodes.clone(), state_root)
+    }
+    // verifies that a payload was never sequenced up to and including the Block at this height
+    pub fn verify_message_exclusion(
+        &self,
+        height: u32,
+        payload_hash: &[u8],
+        proof: &SparseMerkleProof,
+    ) -> Result<()> {
+        let header = self
+            .header(height)
+            .ok_or_else(|| anyhow!("No verified header at height {}", height))?;
+        sparse_merkle::verify_exclusion(&header.message_index_root, payload_hash, proof)
+    }
+}
+
+#[cfg(test)]
+fn test_validators(keypairs: &[crate::crypto::ecdsa::Keypair]) -> ValidatorSet {
+    use crate::consensus::validators::Validator;
+    ValidatorSet {
+        validators: keypairs
+            .iter()
+            .map(|keypair| Validator {
+                public_key: keypair.vk,
+                stake: 1,
+            })
+            .collect(),
+    }
+}
+#[cfg(test)]
+fn certify(
+    header: &BlockHeader,
+    validators: &ValidatorSet,
+    signers: &[crate::crypto::ecdsa::Keypair],
+) -> FinalizedHeader {
+    use crate::types::{BlockCommitment, QuorumCertificate};
+    FinalizedHeader {
+        header: header.clone(),
+        quorum_certificate: QuorumCertificate {
+            block_hash: header.hash(),
+            validator_set_id: validators.id(),
+            signatures: signers
+                .iter()
+                .map(|keypair| BlockCommitment {
+                    signature: keypair.sign_data(&header.hash()).to_bytes().to_vec(),
+                    validator: keypair.serialize_vk(),
+                    timestamp: 1,
+                })
+                .collect(),
+        },
+    }
+}
+
+#[test]
+fn test_light_client_follows_headers() {
+    use crate::crypto::ecdsa::Keypair;
+    let keypairs = [Keypair::new(), Keypair::new()];
+    let validators = test_validators(&keypairs);
+    let genesis = Block::genesis(0).header;
+    let mut light_client = LightClient::from_checkpoint(
+        genesis.clone(),
+        validators.clone(),
+        QuorumRule::StrictMajority,
+    );
+    let header = BlockHeader {
+        height: 1,
+        parent_hash: genesis.hash(),
+        messages_root: Block::messages_root(&[]),
+        state_root: None,
+        message_index_root: vec![],
+        proposer: keypairs[0].serialize_vk(),
+        timestamp: 1,
+        view: 0,
+    };
+    // a single signature does not meet the quorum of two validators
+    assert!(light_client
+        .update(&certify(&header, &validators, &keypairs[..1]))
+        .is_err());
+    let unlinked_header = BlockHeader {
+        parent_hash: vec![0; 32],
+        ..header.clone()
+    };
+    assert!(light_client
+        .update(&certify(&unlinked_header, &validators, &keypairs))
+        .is_err());
+    light_client
+        .update_all(&[certify(&header, &validators, &keypairs)])
+        .unwrap();
+    assert_eq!(light_client.latest_header(), &header);
+    assert_eq!(light_client.header(0), Some(&genesis));
+    assert!(light_client.header(2).is_none());
+}
+
+#[tokio::test]
+async fn test_light_client_sync() {
+    use crate::crypto::ecdsa::Keypair;
+    struct MemorySource(Vec<FinalizedHeader>);
+    #[async_trait]
+    impl HeaderSource for MemorySource {
+        async fn fetch_headers(&self, from: u32, to: u32) -> Result<Vec<FinalizedHeader>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|finalized_header| (from..=to).contains(&finalized_header.header.height))
+                .cloned()
+                .collect())
+        }
+    }
+    let keypairs = [Keypair::new()];
+    let validators = test_validators(&keypairs);
+    let genesis = Block::genesis(0).header;
+    let mut light_client = LightClient::from_checkpoint(
+        genesis.clone(),
+        validators.clone(),
+        QuorumRule::StrictMajority,
+    );
+    // more headers than a single request returns
+    let mut headers = vec![genesis];
+    for height in 1..=MAX_BLOCK_RANGE + 5 {
+        let parent = headers.last().unwrap();
+        headers.push(BlockHeader {
+            height,
+            parent_hash: parent.hash(),
+            proposer: keypairs[0].serialize_vk(),
+            timestamp: height,
+            ..parent.clone()
+        });
+    }
+    let mut source = MemorySource(
+        headers[1..]
+            .iter()
+            .map(|header| certify(header, &validators, &keypairs))
+            .collect(),
+    );
+    assert_eq!(
+        light_client.sync(&source).await.unwrap(),
+        MAX_BLOCK_RANGE + 5
+    );
+    assert_eq!(light_client.latest_header(), headers.last().unwrap());
+    // nothing new to fetch
+    assert_eq!(
+        light_client.sync(&source).await.unwrap(),
+        MAX_BLOCK_RANGE + 5
+    );
+    // a header without a quorum certificate is not followed
+    let next_header = BlockHeader {
+        height: MAX_BLOCK_RANGE + 6,
+        parent_hash: headers.last().unwrap().hash(),
+        ..headers.last().unwrap().clone()
+    };
+    source.0.push(certify(&next_header, &validators, &[]));
+    assert!(light_client.sync(&source).await.is_err());
+    assert_eq!(light_client.latest_header(), headers.last().unwrap());
+}
+
+#[test]
+fn test_verify_message_proofs() {
+    use crate::crypto::{ecdsa::Keypair, sparse_merkle::MemoryStore};
+    use crate::test_utils::TempDb;
+    use ciphercurve_trie::{
+        insert_leaf,
+        merkle::merkle_proof,
+        store::{db::sql::TrieDB, types::Root},
+    };
+    use sha2::{Digest, Sha256};
+    let keypairs = [Keypair::new()];
+    let validators = test_validators(&keypairs);
+    let genesis = Block::genesis(0).header;
+    let mut light_client = LightClient::from_checkpoint(
+        genesis.clone(),
+        validators.clone(),
+        QuorumRule::StrictMajority,
+    );
+    let messages: Vec<Message> = (0u8..2)
+        .map(|data| Message {
+            data: vec![data],
+            timestamp: 0,
+            envelope: None,
+        })
+        .collect();
+    // the trie and the message index as the node builds them for the Block at height 1
+    let db = TempDb::new("light-client");
+    let mut trie = TrieDB {
+        path: db.path.clone(),
+        cache: None,
+    };
+    trie.setup();
+    let mut trie_root = Root::empty();
+    let mut index = MemoryStore::default();
+    let mut message_index_root = genesis.message_index_root.clone();
+    for (position, message) in messages.iter().enumerate() {
+        trie_root = insert_leaf(
+            &mut trie,
+            &mut message.trie_leaf(1, position as u32),
+            Node::Root(trie_root),
+        )
+        .unwrap();
+        message_index_root = sparse_merkle::insert(
+            &mut index,
+            &message_index_root,
+            &message.payload_hash(),
+            &1u32.to_be_bytes(),
+        )
+        .unwrap();
+    }
+    let header = BlockHeader {
+        height: 1,
+        parent_hash: genesis.hash(),
+        messages_root: Block::messages_root(&messages),
+        state_root: trie_root.hash.clone(),
+        message_index_root: message_index_root.clone(),
+        proposer: keypairs[0].serialize_vk(),
+        timestamp: 1,
+        view: 0,
+    };
+    light_client
+        .update(&certify(&header, &validators, &keypairs))
+        .unwrap();
+    let prove = |trie: &mut TrieDB, index: u32| {
+        merkle_proof(
+            trie,
+            Message::trie_key(1, index),
+            Node::Root(trie_root.clone()),
+        )
+        .unwrap()
+    };
+    let proof = prove(&mut trie, 0);
+    assert!(light_client
+        .verify_message_inclusion(1, &messages[0], (1, 0), &proof)
+        .is_ok());
+    // a valid proof for a different leaf fails, also with the leaf appended to it
+    let other_proof = prove(&mut trie, 1);
+    assert!(light_client
+        .verify_message_inclusion(1, &messages[0], (1, 0), &other_proof)
+        .is_err());
+    let mut spliced_proof = other_proof.clone();
+    spliced_proof.nodes.push(proof.nodes[0].clone());
+    assert!(light_client
+        .verify_message_inclusion(1, &messages[0], (1, 0), &spliced_proof)
+        .is_err());
+    assert!(light_client
+        .verify_message_inclusion(1, &messages[0], (1, 1), &proof)
+        .is_err());
+    // no header at height 2 was verified
+    assert!(light_client
+        .verify_message_inclusion(2, &messages[0], (1, 0), &proof)
+        .is_err());
+
+    let unsequenced = Sha256::digest([2]).to_vec();
+    let exclusion_proof = sparse_merkle::prove(&index, &message_index_root, &unsequenced).unwrap();
+    assert!(light_client
+        .verify_message_exclusion(1, &unsequenced, &exclusion_proof)
+        .is_ok());
+    let sequenced = messages[0].payload_hash();
+    // the payload was not sequenced before the Block
+    let genesis_proof =
+        sparse_merkle::prove(&index, &genesis.message_index_root, &sequenced).unwrap();
+    assert!(light_client
+        .verify_message_exclusion(0, &sequenced, &genesis_proof)
+        .is_ok());
+    let inclusion_proof = sparse_merkle::prove(&index, &message_index_root, &sequenced).unwrap();
+    assert!(light_client
+        .verify_message_exclusion(1, &sequenced, &inclusion_proof)
+        .is_err());
+    // a proof for a different key does not exclude the payload
+    assert!(light_client
+        .verify_message_exclusion(1, &unsequenced, &inclusion_proof)
+        .is_err());
+}
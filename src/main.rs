@@ -5,10 +5,14 @@ mod crypto;
 mod gossipper;
 mod handlers;
 mod state;
+#[cfg(test)]
+mod test_utils;
 mod types;
 use api::{
-    commit, get_block, get_commitments, get_height, get_pool, get_state_root_hash, merkle_proof,
-    propose, schedule,
+    commit, exclusion_proof, get_block, get_blocks, get_commitments, get_equivocations,
+    get_headers, get_height, get_message, get_message_by_sequence, get_message_locations,
+    get_message_status, get_pool, get_state_root_hash, get_timeout_certificates, merkle_proof,
+    merkle_proof_at_height, propose, schedule, timeout,
 };
 use axum::{
     extract::DefaultBodyLimit,
@@ -17,41 +21,57 @@ use axum::{
 };
 use colored::*;
 use config::{
-    consensus::{CLEARING_PHASE, ROUND_DURATION},
-    network::PEERS,
+    consensus::local_signer,
+    genesis::Genesis,
+    network::{MAX_BLOCK_RANGE, PEERS, PEER_REJECTION_TIMEOUT},
 };
-use consensus::logic::{current_round, evaluate_commitment, get_committing_validator};
-use gossipper::send_proposal;
-use k256::ecdsa::{signature::SignerMut, Signature};
+use consensus::{
+    election::ElectionRound,
+    logic::{current_round, evaluate_commitment, get_committing_validator, ordered_messages},
+};
+use crypto::signer::SignRequest;
+use gossipper::{fetch_block_range, fetch_height, send_proposal};
 use l2_sequencer::initial_print;
-use prover::generate_random_number;
 use reqwest::Client;
-use state::server::{BlockStore, InMemoryConsensus, MessagePool};
+use state::server::{
+    BlockStore, EvidenceStore, InMemoryConsensus, MessageIndex, MessagePool, SignatureKind,
+    SigningHistory, SqLiteEvidenceStore, SqLiteMessageIndex, SqLiteSigningHistory,
+};
 use std::{
+    collections::HashMap,
     env,
     sync::Arc,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tokio::sync::Mutex;
-use types::{Block, ConsensusCommitment};
+use tokio::{sync::Mutex, task::JoinSet};
+use types::{Block, BlockHeader, ConsensusCommitment, Message, TimeoutVote};
 use {
     ciphercurve_trie::store::{db::sql::TrieDB as MerkleTrieDB, types::Root},
     state::server::{SqLiteBlockStore, SqLiteMessagePool},
 };
 #[allow(unused)]
 use {
-    gossipper::{docker_skip_self, Gossipper},
-    handlers::handle_synchronization_response,
+    gossipper::{docker_skip_self, Gossipper, Peer},
+    handlers::{
+        apply_synchronized_blocks, handle_timeout_vote, open_block_store, post_state_root,
+        restore_trie_root,
+    },
     reqwest::Response,
 };
 
 struct ServerState {
     merkle_trie_state: MerkleTrieDB,
     merkle_trie_root: Root,
+    // trie roots of proposals on top of merkle_trie_root, keyed by their messages root
+    pending_state_roots: HashMap<Vec<u8>, Root>,
+    message_index: MessageIndex,
     local_gossipper: Gossipper,
+    // peers that served invalid Blocks and when, they are skipped until the rejection expires
+    rejected_peers: Vec<(Peer, u32)>,
 }
 
-// currently only supports mock net
+// fetches Block ranges from all peers that are ahead in parallel, then verifies and
+// applies them in order until the node caught up with the highest peer
 #[allow(unused)]
 async fn synchronization_loop(
     shared_state: Arc<Mutex<ServerState>>,
@@ -59,50 +79,121 @@ async fn synchronization_loop(
     shared_pool_state: Arc<Mutex<MessagePool>>,
     shared_consensus_state: Arc<Mutex<InMemoryConsensus>>,
 ) {
-    // only sync when no prioritized task is running (api, consensus) - falling behind is ok for now
-    {
-        let mut block_state_lock = shared_block_state.lock().await;
-        let next_height = block_state_lock.current_block_height();
-        drop(block_state_lock);
-        let gossipper = Gossipper {
-            peers: PEERS.to_vec(),
-            client: Client::new(),
+    let client = Client::new();
+    let this_node = env::var("API_HOST_WITH_PORT").unwrap_or("0.0.0.0:8080".to_string());
+    loop {
+        let rejected_peers: Vec<Peer> = {
+            let mut shared_state_lock = shared_state.lock().await;
+            let now = get_current_time();
+            shared_state_lock
+                .rejected_peers
+                .retain(|(_, rejected_at)| now < rejected_at + PEER_REJECTION_TIMEOUT);
+            shared_state_lock
+                .rejected_peers
+                .iter()
+                .map(|(peer, _)| *peer)
+                .collect()
         };
-
-        for peer in gossipper.peers {
-            // todo: make this generic for n amount of nodes
-            let this_node = env::var("API_HOST_WITH_PORT").unwrap_or("0.0.0.0:8080".to_string());
-            if docker_skip_self(&this_node, &peer) {
+        let next_height = shared_block_state.lock().await.current_block_height();
+        let mut peer_heights = JoinSet::new();
+        for peer in PEERS.iter().copied() {
+            if docker_skip_self(&this_node, peer) || rejected_peers.contains(&peer) {
                 continue;
             }
-            let response: Option<Response> = match gossipper
-                .client
-                .get(format!("http://{}{}{}", &peer, "/get/block/", next_height))
-                //.timeout(Duration::from_secs(20))
-                .send()
-                .await
-            {
-                Ok(response) => Some(response),
-                Err(_) => None,
+            let client = client.clone();
+            peer_heights.spawn(async move { (peer, fetch_height(client, peer).await) });
+        }
+        let mut peers_ahead: Vec<(Peer, u32)> = Vec::new();
+        while let Some(peer_height) = peer_heights.join_next().await {
+            if let Ok((peer, Some(height))) = peer_height {
+                if height > next_height {
+                    peers_ahead.push((peer, height));
+                }
+            }
+        }
+        let target_height = match peers_ahead.iter().map(|(_, height)| *height).max() {
+            Some(target_height) => target_height,
+            None => return,
+        };
+        // one range for each peer that is ahead, a range is only requested from a peer that has it
+        let mut ranges = JoinSet::new();
+        for index in 0..peers_ahead.len() as u32 {
+            let from = next_height + index * MAX_BLOCK_RANGE;
+            let serving_peers: Vec<Peer> = peers_ahead
+                .iter()
+                .filter(|(_, height)| *height > from)
+                .map(|(peer, _)| *peer)
+                .collect();
+            if serving_peers.is_empty() {
+                break;
+            }
+            let peer = serving_peers[index as usize % serving_peers.len()];
+            let client = client.clone();
+            let to = from + MAX_BLOCK_RANGE - 1;
+            ranges.spawn(
+                async move { (from, peer, fetch_block_range(client, peer, from, to).await) },
+            );
+        }
+        let mut fetched_ranges = Vec::new();
+        while let Some(Ok(range)) = ranges.join_next().await {
+            fetched_ranges.push(range);
+        }
+        fetched_ranges.sort_by_key(|(from, _, _)| *from);
+        let mut expected_height = next_height;
+        for (from, peer, blocks) in fetched_ranges {
+            // a range that failed to download leaves a gap, the next pass refetches from there
+            let blocks = match blocks {
+                Ok(blocks) if from == expected_height && !blocks.is_empty() => blocks,
+                _ => break,
             };
-            match response {
-                Some(response) => {
-                    handle_synchronization_response(
-                        shared_state.clone(),
-                        shared_block_state.clone(),
-                        shared_consensus_state.clone(),
-                        response,
-                        next_height,
-                    )
-                    .await;
+            let requested = blocks.len() as u32;
+            match apply_synchronized_blocks(
+                Arc::clone(&shared_state),
+                Arc::clone(&shared_block_state),
+                Arc::clone(&shared_consensus_state),
+                blocks,
+            )
+            .await
+            {
+                Ok(applied) => {
+                    expected_height = shared_block_state.lock().await.current_block_height();
+                    if applied < requested || requested < MAX_BLOCK_RANGE {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    println!(
+                        "{}",
+                        format_args!(
+                            "{} Rejecting peer: {} for {}s, served invalid Blocks: {}",
+                            "[Warning]".yellow(),
+                            peer,
+                            PEER_REJECTION_TIMEOUT,
+                            e
+                        )
+                    );
+                    shared_state
+                        .lock()
+                        .await
+                        .rejected_peers
+                        .push((peer, get_current_time()));
+                    break;
                 }
-                _ => {}
             }
         }
-    }
-    #[cfg(not(feature = "local-net"))]
-    {
-        todo!("Implement mainnet synchronization!");
+        println!(
+            "{}",
+            format_args!(
+                "{} Synchronization progress: {}/{}",
+                "[Info]".green(),
+                expected_height - 1,
+                target_height - 1
+            )
+        );
+        if expected_height == next_height {
+            // no progress, retry in the next synchronization interval
+            return;
+        }
     }
 }
 async fn consensus_loop(
@@ -112,21 +203,24 @@ async fn consensus_loop(
     shared_consensus_state: Arc<Mutex<InMemoryConsensus>>,
 ) {
     let unix_timestamp = get_current_time();
-    let (block_state_lock, pool_state_lock) =
+    let (mut block_state_lock, mut pool_state_lock) =
         tokio::join!(shared_block_state.lock(), shared_pool_state.lock());
     let mut consensus_state_lock = shared_consensus_state.lock().await;
     let last_block_unix_timestamp = block_state_lock
         .get_block_by_height(block_state_lock.current_block_height() - 1)
+        .header
         .timestamp;
 
-    let rounds_since = (unix_timestamp - last_block_unix_timestamp) / ROUND_DURATION;
-    if unix_timestamp >= last_block_unix_timestamp + rounds_since * ROUND_DURATION
+    let round_duration = consensus_state_lock.genesis.round_duration;
+    let clearing_phase = consensus_state_lock.genesis.clearing_phase;
+    let rounds_since = (unix_timestamp - last_block_unix_timestamp) / round_duration;
+    if unix_timestamp >= last_block_unix_timestamp + rounds_since * round_duration
         && unix_timestamp
-            <= last_block_unix_timestamp + rounds_since * ROUND_DURATION + CLEARING_PHASE
+            <= last_block_unix_timestamp + rounds_since * round_duration + clearing_phase
     {
         println!(
             "[Info]: Reinitializing consensus state, clearing phase remaining: {}",
-            ((last_block_unix_timestamp + rounds_since * ROUND_DURATION + CLEARING_PHASE)
+            ((last_block_unix_timestamp + rounds_since * round_duration + clearing_phase)
                 - unix_timestamp)
         );
         consensus_state_lock.reinitialize();
@@ -135,48 +229,193 @@ async fn consensus_loop(
 
     let committing_validator = get_committing_validator(
         last_block_unix_timestamp,
-        consensus_state_lock.validators.clone(),
+        round_duration,
+        &consensus_state_lock.validators,
     );
 
     println!(
         "[Info] Current round: {}",
-        current_round(last_block_unix_timestamp)
+        current_round(last_block_unix_timestamp, round_duration)
     );
 
     let previous_block_height = block_state_lock.current_block_height() - 1;
-    if consensus_state_lock.local_validator == committing_validator
-        && !consensus_state_lock.committed
-    {
-        let random_zk_number = generate_random_number(
-            consensus_state_lock
-                .local_validator
-                .to_sec1_bytes()
-                .to_vec(),
-            (previous_block_height + 1).to_be_bytes().to_vec(),
+    // no block was finalized within the timeout of the current view, vote to hand the
+    // round to the fallback proposer of the next view
+    let view_deadline = last_block_unix_timestamp
+        + rounds_since * round_duration
+        + clearing_phase
+        + (consensus_state_lock.view + 1) * consensus_state_lock.genesis.view_timeout;
+    // a validator that signed a Block at this height is locked on it, voting to leave the
+    // view would let the Block of the next view finalize next to the signed one
+    let signed_at_height = consensus_state_lock
+        .signing_history
+        .signed_at(previous_block_height + 1);
+    if unix_timestamp >= view_deadline && signed_at_height && !consensus_state_lock.timeout_voted {
+        println!(
+            "[Info] View {} timed out, not voting after signing a Block at height: {}",
+            consensus_state_lock.view,
+            previous_block_height + 1
         );
-        let commitment = ConsensusCommitment {
+    }
+    if unix_timestamp >= view_deadline
+        && !signed_at_height
+        && !consensus_state_lock.timeout_voted
+        && consensus_state_lock
+            .validators
+            .contains(&consensus_state_lock.local_validator)
+    {
+        let round = current_round(last_block_unix_timestamp, round_duration);
+        let mut vote = TimeoutVote {
             validator: consensus_state_lock
                 .local_validator
                 .to_sec1_bytes()
                 .to_vec(),
-            receipt: random_zk_number,
+            chain_id: consensus_state_lock.genesis.chain_id.clone(),
+            height: previous_block_height + 1,
+            round,
+            view: consensus_state_lock.view + 1,
+            signature: vec![],
+            certificate: consensus_state_lock.view_certificate.clone(),
         };
-        let local_gossipper = {
-            let shared_state_lock = shared_state.try_lock();
-            if let Ok(state) = shared_state_lock {
-                state.local_gossipper.clone()
-            } else {
+        // the signer might be remote, no lock is held while waiting for it
+        let signer = Arc::clone(&consensus_state_lock.signer);
+        drop(consensus_state_lock);
+        drop(pool_state_lock);
+        drop(block_state_lock);
+        match signer.sign(&SignRequest::TimeoutVote(vote.clone())).await {
+            Ok(signature) => vote.signature = signature.to_bytes().to_vec(),
+            Err(e) => {
+                println!(
+                    "{}",
+                    format_args!("{} Failed to sign Timeout Vote: {}", "[Error]".red(), e)
+                );
+                return;
+            }
+        }
+        let block_state_lock = shared_block_state.lock().await;
+        let mut consensus_state_lock = shared_consensus_state.lock().await;
+        // a Block might have been stored or the view changed while signing
+        if block_state_lock.current_block_height() != vote.height
+            || current_round(last_block_unix_timestamp, round_duration) != vote.round
+            || consensus_state_lock.view + 1 != vote.view
+        {
+            return;
+        }
+        println!(
+            "{}",
+            format_args!(
+                "{} View {} timed out, voting for view: {}",
+                "[Warning]".yellow(),
+                consensus_state_lock.view,
+                vote.view
+            )
+        );
+        consensus_state_lock.timeout_voted = true;
+        let gossipper = Gossipper {
+            peers: PEERS.to_vec(),
+            client: Client::new(),
+        };
+        let timeout_vote = vote.clone();
+        // peers lock their consensus state to count the vote, don't hold ours meanwhile
+        tokio::spawn(async move { gossipper.gossip_timeout_vote(timeout_vote).await });
+        if let Err(e) = handle_timeout_vote(
+            &mut consensus_state_lock,
+            vote,
+            previous_block_height + 1,
+            round,
+        ) {
+            println!(
+                "{}",
+                format_args!(
+                    "{} Failed to count own Timeout Vote: {}",
+                    "[Error]".red(),
+                    e
+                )
+            );
+        }
+        return;
+    }
+    if consensus_state_lock.local_validator == committing_validator
+        && !consensus_state_lock.committed
+    {
+        let election_round = ElectionRound {
+            chain_id: consensus_state_lock.genesis.chain_id.clone(),
+            height: previous_block_height + 1,
+            round: current_round(last_block_unix_timestamp, round_duration),
+        };
+        // the signer might be remote and zk proofs take a while, no lock is held meanwhile
+        let signer = Arc::clone(&consensus_state_lock.signer);
+        let leader_election = consensus_state_lock
+            .genesis
+            .leader_election
+            .leader_election();
+        let local_validator = consensus_state_lock.local_validator;
+        drop(consensus_state_lock);
+        drop(pool_state_lock);
+        drop(block_state_lock);
+        let proof = match leader_election
+            .prove(signer.as_ref(), &election_round)
+            .await
+        {
+            Ok(proof) => proof,
+            Err(e) => {
+                println!(
+                    "{}",
+                    format_args!("{} Failed to prove Commitment: {}", "[Error]".red(), e)
+                );
                 return;
             }
         };
+        let mut commitment = ConsensusCommitment {
+            validator: local_validator.to_sec1_bytes().to_vec(),
+            chain_id: election_round.chain_id,
+            height: election_round.height,
+            round: election_round.round,
+            proof,
+            signature: vec![],
+        };
+        match signer
+            .sign(&SignRequest::Commitment(commitment.clone()))
+            .await
+        {
+            Ok(signature) => commitment.signature = signature.to_bytes().to_vec(),
+            Err(e) => {
+                println!(
+                    "{}",
+                    format_args!("{} Failed to sign Commitment: {}", "[Error]".red(), e)
+                );
+                return;
+            }
+        }
+        let local_gossipper = shared_state.lock().await.local_gossipper.clone();
         let _ = local_gossipper
             .gossip_consensus_commitment(commitment.clone())
             .await;
 
-        let proposing_validator =
-            evaluate_commitment(commitment, consensus_state_lock.validators.clone());
-        consensus_state_lock.round_winner = Some(proposing_validator);
+        block_state_lock = shared_block_state.lock().await;
+        pool_state_lock = shared_pool_state.lock().await;
+        consensus_state_lock = shared_consensus_state.lock().await;
+        // a Block might have been stored or the round ended while proving
+        if block_state_lock.current_block_height() != commitment.height
+            || current_round(last_block_unix_timestamp, round_duration) != commitment.round
+            || consensus_state_lock.committed
+        {
+            return;
+        }
         consensus_state_lock.committed = true;
+        match evaluate_commitment(
+            &commitment,
+            consensus_state_lock.leader_election.as_ref(),
+            &consensus_state_lock.validators,
+        ) {
+            Ok(proposing_validator) => {
+                consensus_state_lock.round_winner = Some(proposing_validator)
+            }
+            Err(e) => println!(
+                "{}",
+                format_args!("{} Failed to evaluate Commitment: {}", "[Error]".red(), e)
+            ),
+        }
     }
 
     if consensus_state_lock.round_winner.is_none() {
@@ -184,19 +423,84 @@ async fn consensus_loop(
     }
 
     let proposing_validator = consensus_state_lock.round_winner.unwrap();
-    let messages = pool_state_lock.get_all_messages();
+    // replayed and out of order messages would invalidate the Block
+    let messages = ordered_messages(
+        pool_state_lock.get_all_messages(),
+        &consensus_state_lock.genesis.chain_id,
+        |sender| block_state_lock.get_finalized_nonce(sender),
+    );
 
     if consensus_state_lock.local_validator == proposing_validator && !consensus_state_lock.proposed
     {
+        let parent = block_state_lock.get_block_by_height(previous_block_height);
+        let (state_root, message_index_root) = {
+            let shared_state_lock = shared_state.try_lock();
+            if let Ok(mut state) = shared_state_lock {
+                (
+                    post_state_root(&mut state, previous_block_height + 1, &messages),
+                    state.message_index.insert_messages(
+                        &parent.header.message_index_root,
+                        previous_block_height + 1,
+                        &messages,
+                    ),
+                )
+            } else {
+                return;
+            }
+        };
+        let message_ids: Vec<Vec<u8>> = messages.iter().map(Message::id).collect();
         let mut proposed_block = Block {
-            height: previous_block_height + 1,
+            header: BlockHeader {
+                height: previous_block_height + 1,
+                parent_hash: parent.hash(),
+                messages_root: Block::messages_root(&messages),
+                state_root: state_root.hash,
+                message_index_root,
+                proposer: consensus_state_lock
+                    .local_validator
+                    .to_sec1_bytes()
+                    .to_vec(),
+                timestamp: unix_timestamp,
+                view: consensus_state_lock.view,
+            },
             signature: None,
             messages,
             commitments: None,
-            timestamp: unix_timestamp,
         };
-        let mut signing_key = consensus_state_lock.local_signing_key.clone();
-        let signature: Signature = signing_key.sign(&proposed_block.to_bytes());
+        if !consensus_state_lock.signing_history.check_and_record(
+            proposed_block.header.height,
+            SignatureKind::Proposal,
+            &proposed_block.hash(),
+        ) {
+            println!(
+                "{}",
+                format_args!(
+                    "{} Refusing to propose conflicting Block at height: {}",
+                    "[Warning]".yellow(),
+                    proposed_block.header.height
+                )
+            );
+            consensus_state_lock.proposed = true;
+            return;
+        }
+        // the signer might be remote, no lock is held while waiting for it
+        let signer = Arc::clone(&consensus_state_lock.signer);
+        drop(block_state_lock);
+        drop(pool_state_lock);
+        drop(consensus_state_lock);
+        let signature = match signer
+            .sign(&SignRequest::Header(proposed_block.header.clone()))
+            .await
+        {
+            Ok(signature) => signature,
+            Err(e) => {
+                println!(
+                    "{}",
+                    format_args!("{} Failed to sign Block: {}", "[Error]".red(), e)
+                );
+                return;
+            }
+        };
         proposed_block.signature = Some(signature.to_bytes().to_vec());
         println!("{}", format_args!("{} Proposing Block!", "[Info]".green()));
         // only for testing, send proposal to one node that is not self
@@ -206,12 +510,9 @@ async fn consensus_loop(
         // further gossip this proposal until it gains enough attestations
         let trusted_peer: &'static str = PEERS
             .iter()
-            .find(|&&ref peer| peer != &this_node)
+            .find(|&peer| peer != &this_node)
             .cloned()
             .expect("[Error] No valid peer found!");
-        drop(block_state_lock);
-        drop(pool_state_lock);
-        drop(consensus_state_lock);
         let _ = send_proposal(
             reqwest::Client::new(),
             trusted_peer,
@@ -219,17 +520,26 @@ async fn consensus_loop(
         )
         .await;
 
-        let maybe_pool_lock = shared_pool_state.try_lock();
-        let mut pool_state_lock = maybe_pool_lock.expect("Failed to unwrap pool lock");
-        let maybe_consensus_lock = shared_consensus_state.try_lock();
-        let mut consensus_state_lock = maybe_consensus_lock.expect("Failed to get consensus lock");
+        let mut pool_state_lock = shared_pool_state.lock().await;
+        let mut consensus_state_lock = shared_consensus_state.lock().await;
         consensus_state_lock.proposed = true;
+        pool_state_lock.mark_proposed(&message_ids, proposed_block.header.height);
         pool_state_lock.reinitialize()
     }
 }
 #[tokio::main]
 async fn main() {
     initial_print();
+    #[cfg(feature = "mock-prover")]
+    println!(
+        "{}",
+        format_args!(
+            "{} Running with the mock prover, zk random numbers are not proven or verified",
+            "[Warning]".yellow()
+        )
+    );
+    let genesis: Genesis =
+        Genesis::load(&env::var("PATH_TO_GENESIS").unwrap_or("genesis.json".to_string()));
     let mut block_state = {
         let block_state: BlockStore = BlockStore {
             db_path: env::var("PATH_TO_DB").unwrap_or("database.sqlite".to_string()),
@@ -237,7 +547,7 @@ async fn main() {
         block_state.setup();
         block_state
     };
-    block_state.trigger_genesis(get_current_time());
+    open_block_store(&mut block_state, &genesis);
     let pool_state: MessagePool = {
         let pool_state: MessagePool = MessagePool {
             size: 0,
@@ -246,13 +556,32 @@ async fn main() {
         pool_state.setup();
         pool_state
     };
-    let consensus_state: InMemoryConsensus = InMemoryConsensus::empty_with_default_validators();
-    let merkle_trie_state: MerkleTrieDB = MerkleTrieDB {
+    let signing_history: SigningHistory = {
+        let signing_history: SigningHistory = SigningHistory {
+            db_path: env::var("PATH_TO_DB").unwrap_or("database.sqlite".to_string()),
+        };
+        signing_history.setup();
+        signing_history
+    };
+    let evidence: EvidenceStore = {
+        let evidence: EvidenceStore = EvidenceStore {
+            db_path: env::var("PATH_TO_DB").unwrap_or("database.sqlite".to_string()),
+        };
+        evidence.setup();
+        evidence
+    };
+    let consensus_state: InMemoryConsensus =
+        InMemoryConsensus::from_genesis(genesis, local_signer().await, signing_history, evidence);
+    let mut merkle_trie_state: MerkleTrieDB = MerkleTrieDB {
         path: env::var("PATH_TO_DB").unwrap_or("database.sqlite".to_string()),
         cache: None,
     };
     merkle_trie_state.setup();
-    let merkle_trie_root: Root = Root::empty();
+    let message_index = MessageIndex {
+        db_path: env::var("PATH_TO_DB").unwrap_or("database.sqlite".to_string()),
+    };
+    message_index.setup();
+    let merkle_trie_root: Root = restore_trie_root(&mut block_state, &mut merkle_trie_state);
     let local_gossipper: Gossipper = Gossipper {
         peers: PEERS.to_vec(),
         client: Client::new(),
@@ -260,7 +589,10 @@ async fn main() {
     let shared_state: Arc<Mutex<ServerState>> = Arc::new(Mutex::new(ServerState {
         merkle_trie_state,
         merkle_trie_root,
+        pending_state_roots: HashMap::new(),
+        message_index,
         local_gossipper,
+        rejected_peers: Vec::new(),
     }));
 
     let shared_block_state: Arc<Mutex<BlockStore>> = Arc::new(Mutex::new(block_state));
@@ -272,7 +604,7 @@ async fn main() {
     let formatted_msg = format!(
         "{}{}",
         "Starting Node: ".green().italic(),
-        &host_with_port.yellow().bold()
+        host_with_port.yellow().bold()
     );
     println!("{}", formatted_msg);
 
@@ -283,7 +615,6 @@ async fn main() {
         let shared_consensus_state = Arc::clone(&shared_consensus_state);
         async move {
             loop {
-                // for now the loop syncs one block at a time, this can be optimized
                 synchronization_loop(
                     Arc::clone(&shared_state),
                     Arc::clone(&shared_block_state),
@@ -323,12 +654,23 @@ async fn main() {
                 .route("/get/pool", get(get_pool))
                 .route("/get/commitments", get(get_commitments))
                 .route("/get/block/:height", get(get_block))
+                .route("/get/blocks", get(get_blocks))
+                .route("/get/headers", get(get_headers))
                 .route("/get/height", get(get_height))
+                .route("/get/sequence/:sequence", get(get_message_by_sequence))
+                .route("/get/locations/:hash", get(get_message_locations))
+                .route("/get/message/:hash", get(get_message))
+                .route("/message/:id/status", get(get_message_status))
                 .route("/get/state_root_hash", get(get_state_root_hash))
                 .route("/schedule", post(schedule))
                 .route("/commit", post(commit))
+                .route("/timeout", post(timeout))
+                .route("/get/timeouts/:height", get(get_timeout_certificates))
+                .route("/get/equivocations", get(get_equivocations))
                 .route("/propose", post(propose))
                 .route("/merkle_proof", post(merkle_proof))
+                .route("/merkle_proof/:height", post(merkle_proof_at_height))
+                .route("/exclusion_proof/:height", post(exclusion_proof))
                 .layer(DefaultBodyLimit::max(10000000))
                 .layer(Extension(shared_state))
                 .layer(Extension(shared_block_state))
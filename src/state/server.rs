@@ -1,20 +1,43 @@
+#[cfg(test)]
+use crate::test_utils::TempDb;
 use crate::{
-    config::consensus::{
-        v1_sk_deserialized, v1_vk_deserialized, v2_sk_deserialized, v3_sk_deserialized,
-        v3_vk_deserialized, v4_sk_deserialized, v4_vk_deserialized,
+    config::genesis::Genesis,
+    consensus::{election::LeaderElection, logic::fallback_proposer, validators::ValidatorSet},
+    crypto::{
+        signer::Signer,
+        sparse_merkle::{self, SmtNode, SmtStore, SparseMerkleProof},
+    },
+    types::{
+        Block, ConsensusCommitment, FinalizedBlock, GenericPublicKey, GenericSignature, Message,
+        MessageLocation, QuorumCertificate, SequencedMessage, TimeoutCertificate, TimeoutVote,
+        Timestamp,
     },
-    types::{Block, ConsensusCommitment, Message, Timestamp},
 };
-use k256::ecdsa::{SigningKey, VerifyingKey};
-use rusqlite::{params, Connection};
-use std::env;
+use ciphercurve_trie::store::types::Root;
+use k256::ecdsa::VerifyingKey;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 pub trait SqLiteBlockStore {
     fn setup(&self);
     fn block_exists(&self, height: u32) -> bool;
-    fn trigger_genesis(&mut self, timestamp: Timestamp);
-    fn insert_block(&mut self, previous_height: u32, block: Block);
+    fn trigger_genesis(&mut self, timestamp: Timestamp, validator_set_id: Vec<u8>);
+    // a Block is only stored together with the certificate that finalized it
+    fn insert_block(&mut self, previous_height: u32, block: Block, certificate: QuorumCertificate);
     fn get_block_by_height(&self, height: u32) -> Block;
+    fn get_quorum_certificate(&self, height: u32) -> QuorumCertificate;
+    // the stored Blocks from `from` to `to`, both inclusive
+    fn get_finalized_blocks(&self, from: u32, to: u32) -> Vec<FinalizedBlock>;
     fn current_block_height(&self) -> u32;
+    fn insert_state_root(&mut self, height: u32, root: Root);
+    fn latest_state_root(&self) -> Option<(u32, Root)>;
+    // the trie root after the Block at this height was stored
+    fn get_state_root(&self, height: u32) -> Option<Root>;
+    fn get_message_by_sequence(&self, sequence: u64) -> Option<SequencedMessage>;
+    // a payload may be sequenced more than once
+    fn get_message_locations(&self, payload_hash: &[u8]) -> Vec<MessageLocation>;
+    // the highest nonce of the sender in a stored Block
+    fn get_finalized_nonce(&self, sender: &[u8]) -> Option<u64>;
 }
 pub struct BlockStore {
     pub db_path: String,
@@ -30,6 +53,52 @@ impl SqLiteBlockStore for BlockStore {
             [],
         )
         .unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS quorum_certificates (
+            height INTEGER PRIMARY KEY,
+            certificate BLOB NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+        // every finalized message with its position in the total order
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sequenced_messages (
+            sequence INTEGER PRIMARY KEY,
+            height INTEGER NOT NULL,
+            idx INTEGER NOT NULL,
+            payload_hash BLOB NOT NULL,
+            message BLOB NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS sequenced_messages_by_hash
+            ON sequenced_messages (payload_hash)",
+            [],
+        )
+        .unwrap();
+        // nonces of signed messages per Block, big endian so blobs compare like integers
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS finalized_nonces (
+            sender BLOB NOT NULL,
+            height INTEGER NOT NULL,
+            nonce BLOB NOT NULL,
+            PRIMARY KEY (sender, height)
+            )",
+            [],
+        )
+        .unwrap();
+        // trie root after each stored block, used to resume the trie on restart
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS state_roots (
+            height INTEGER PRIMARY KEY,
+            root BLOB NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
     }
     fn block_exists(&self, height: u32) -> bool {
         let conn = Connection::open(&self.db_path).unwrap();
@@ -59,29 +128,261 @@ impl SqLiteBlockStore for BlockStore {
             .unwrap_or(None);
         // todo: don't expect this
         bincode::deserialize(
-            &block_serialized.expect(&format!("[Error] Block not found: {}", &height)),
+            &block_serialized.unwrap_or_else(|| panic!("[Error] Block not found: {}", height)),
         )
         .unwrap()
     }
-    fn insert_block(&mut self, height: u32, block: Block) {
-        let conn = Connection::open(&self.db_path).unwrap();
-        conn.execute(
+    fn insert_block(&mut self, height: u32, block: Block, certificate: QuorumCertificate) {
+        let mut conn = Connection::open(&self.db_path).unwrap();
+        let tx = conn.transaction().unwrap();
+        tx.execute(
             "INSERT OR REPLACE INTO blocks (height, block) VALUES (?1, ?2)",
             params![height, bincode::serialize(&block).unwrap()],
         )
         .unwrap();
+        tx.execute(
+            "INSERT OR REPLACE INTO quorum_certificates (height, certificate) VALUES (?1, ?2)",
+            params![height, bincode::serialize(&certificate).unwrap()],
+        )
+        .unwrap();
+        // sequence numbers continue after the messages of all lower Blocks
+        tx.execute(
+            "DELETE FROM sequenced_messages WHERE height >= ?1",
+            [&height],
+        )
+        .unwrap();
+        let first_sequence: u64 = tx
+            .query_row(
+                "SELECT COALESCE(MAX(sequence) + 1, 0) FROM sequenced_messages",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        for (index, message) in block.messages.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO sequenced_messages (sequence, height, idx, payload_hash, message)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    first_sequence + index as u64,
+                    height,
+                    index as u32,
+                    message.payload_hash(),
+                    bincode::serialize(message).unwrap()
+                ],
+            )
+            .unwrap();
+        }
+        tx.execute("DELETE FROM finalized_nonces WHERE height >= ?1", [&height])
+            .unwrap();
+        // nonces increase within a Block, the last message of a sender holds its highest nonce
+        for envelope in block
+            .messages
+            .iter()
+            .filter_map(|message| message.envelope.as_ref())
+        {
+            tx.execute(
+                "INSERT OR REPLACE INTO finalized_nonces (sender, height, nonce) VALUES (?1, ?2, ?3)",
+                params![envelope.sender, height, envelope.nonce.to_be_bytes()],
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+    }
+    fn get_quorum_certificate(&self, height: u32) -> QuorumCertificate {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let certificate: Vec<u8> = conn
+            .query_row(
+                "SELECT certificate FROM quorum_certificates WHERE height = ?1",
+                [&height],
+                |row| row.get(0),
+            )
+            .unwrap_or_else(|_| panic!("[Error] Quorum certificate not found: {}", height));
+        bincode::deserialize(&certificate).unwrap()
     }
-    fn trigger_genesis(&mut self, timestamp: Timestamp) {
-        self.insert_block(
-            0u32,
-            Block {
-                timestamp,
-                height: 0,
-                signature: Some(vec![]),
-                messages: vec![],
-                commitments: None,
+    fn get_finalized_blocks(&self, from: u32, to: u32) -> Vec<FinalizedBlock> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT blocks.block, quorum_certificates.certificate FROM blocks
+                JOIN quorum_certificates ON blocks.height = quorum_certificates.height
+                WHERE blocks.height BETWEEN ?1 AND ?2 ORDER BY blocks.height",
+            )
+            .unwrap();
+        let blocks = stmt
+            .query_map(params![from, to], |row| {
+                Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .unwrap();
+        blocks
+            .map(|block| {
+                let (block, certificate) = block.unwrap();
+                FinalizedBlock {
+                    block: bincode::deserialize(&block).unwrap(),
+                    quorum_certificate: bincode::deserialize(&certificate).unwrap(),
+                }
+            })
+            .collect()
+    }
+    fn insert_state_root(&mut self, height: u32, root: Root) {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO state_roots (height, root) VALUES (?1, ?2)",
+            params![height, bincode::serialize(&root).unwrap()],
+        )
+        .unwrap();
+    }
+    fn latest_state_root(&self) -> Option<(u32, Root)> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT height, root FROM state_roots ORDER BY height DESC LIMIT 1")
+            .unwrap();
+        let latest: Option<(u32, Vec<u8>)> = stmt
+            .query_row([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .ok();
+        latest.map(|(height, root)| (height, bincode::deserialize(&root).unwrap()))
+    }
+    fn get_state_root(&self, height: u32) -> Option<Root> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let root: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT root FROM state_roots WHERE height = ?1",
+                [&height],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        root.map(|root| bincode::deserialize(&root).unwrap())
+    }
+    fn get_message_by_sequence(&self, sequence: u64) -> Option<SequencedMessage> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.query_row(
+            "SELECT height, idx, message FROM sequenced_messages WHERE sequence = ?1",
+            [&sequence],
+            |row| {
+                let message: Vec<u8> = row.get(2)?;
+                Ok(SequencedMessage {
+                    location: MessageLocation {
+                        sequence,
+                        height: row.get(0)?,
+                        index: row.get(1)?,
+                    },
+                    message: bincode::deserialize(&message).unwrap(),
+                })
             },
         )
+        .optional()
+        .unwrap()
+    }
+    fn get_message_locations(&self, payload_hash: &[u8]) -> Vec<MessageLocation> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT sequence, height, idx FROM sequenced_messages
+                WHERE payload_hash = ?1 ORDER BY sequence",
+            )
+            .unwrap();
+        stmt.query_map([payload_hash], |row| {
+            Ok(MessageLocation {
+                sequence: row.get(0)?,
+                height: row.get(1)?,
+                index: row.get(2)?,
+            })
+        })
+        .unwrap()
+        .map(|location| location.unwrap())
+        .collect()
+    }
+    fn get_finalized_nonce(&self, sender: &[u8]) -> Option<u64> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let nonce: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT MAX(nonce) FROM finalized_nonces WHERE sender = ?1",
+                params![sender],
+                |row| row.get(0),
+            )
+            .unwrap();
+        nonce.map(|nonce| u64::from_be_bytes(nonce.try_into().unwrap()))
+    }
+    fn trigger_genesis(&mut self, timestamp: Timestamp, validator_set_id: Vec<u8>) {
+        let genesis_block = Block::genesis(timestamp);
+        // the genesis Block is derived from the genesis file and carries no signatures
+        let certificate = QuorumCertificate {
+            block_hash: genesis_block.hash(),
+            validator_set_id,
+            signatures: vec![],
+        };
+        self.insert_block(0u32, genesis_block, certificate);
+        self.insert_state_root(0u32, Root::empty());
+    }
+}
+
+// the sparse merkle index of payload hashes, each maps to the height it was first sequenced at
+pub trait SqLiteMessageIndex {
+    fn setup(&self);
+    fn insert_messages(&mut self, root: &[u8], height: u32, messages: &[Message]) -> Vec<u8>;
+    fn prove(&self, root: &[u8], payload_hash: &[u8]) -> anyhow::Result<SparseMerkleProof>;
+}
+pub struct MessageIndex {
+    pub db_path: String,
+}
+struct SqLiteSmtStore<'a> {
+    conn: &'a Connection,
+}
+impl SmtStore for SqLiteSmtStore<'_> {
+    fn get_node(&self, hash: &[u8]) -> Option<SmtNode> {
+        let node: Option<Vec<u8>> = self
+            .conn
+            .query_row(
+                "SELECT node FROM message_index WHERE hash = ?1",
+                [hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        node.map(|node| bincode::deserialize(&node).unwrap())
+    }
+    fn insert_node(&mut self, hash: &[u8], node: &SmtNode) {
+        // nodes are content addressed, a proposal that is evaluated twice writes the same nodes
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO message_index (hash, node) VALUES (?1, ?2)",
+                params![hash, bincode::serialize(node).unwrap()],
+            )
+            .unwrap();
+    }
+}
+impl SqLiteMessageIndex for MessageIndex {
+    fn setup(&self) {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_index (
+            hash BLOB PRIMARY KEY,
+            node BLOB NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+    }
+    fn insert_messages(&mut self, root: &[u8], height: u32, messages: &[Message]) -> Vec<u8> {
+        let mut conn = Connection::open(&self.db_path).unwrap();
+        let tx = conn.transaction().unwrap();
+        let mut store = SqLiteSmtStore { conn: &tx };
+        let mut root = root.to_vec();
+        for message in messages {
+            root = sparse_merkle::insert(
+                &mut store,
+                &root,
+                &message.payload_hash(),
+                &height.to_be_bytes(),
+            )
+            .expect("[Critical] Message index is missing nodes");
+        }
+        tx.commit().unwrap();
+        root
+    }
+    fn prove(&self, root: &[u8], payload_hash: &[u8]) -> anyhow::Result<SparseMerkleProof> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        sparse_merkle::prove(&SqLiteSmtStore { conn: &conn }, root, payload_hash)
     }
 }
 
@@ -89,10 +390,21 @@ impl SqLiteBlockStore for BlockStore {
 pub trait SqLiteMessagePool {
     fn setup(&self);
     fn insert_message(&mut self, message: Message);
-    fn get_message_by_index(&self, index: u32) -> Message;
     fn get_all_messages(&self) -> Vec<Message>;
+    fn contains_message(&self, id: &[u8]) -> bool;
+    fn get_receipt(&self, id: &[u8]) -> Option<PoolReceipt>;
+    fn mark_proposed(&mut self, ids: &[Vec<u8>], height: u32);
+    // records the nonce unless it does not exceed the last nonce of the sender
+    fn check_and_record_nonce(&mut self, sender: &[u8], nonce: u64) -> bool;
     fn reinitialize(&mut self);
 }
+// what the pool remembers about a submitted message after it left the pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolReceipt {
+    pub payload_hash: Vec<u8>,
+    // the height of the last Block this node proposed the message in
+    pub proposed_height: Option<u32>,
+}
 pub struct MessagePool {
     pub size: u32,
     pub db_path: String,
@@ -108,21 +420,24 @@ impl SqLiteMessagePool for MessagePool {
             [],
         )
         .unwrap();
-    }
-    fn get_message_by_index(&self, index: u32) -> Message {
-        let conn = Connection::open(&self.db_path).unwrap();
-        let mut stmt = conn
-            .prepare("SELECT tx FROM txns WHERE uid = ?1 LIMIT 1")
-            .unwrap();
-
-        let message_serialized: Option<Vec<u8>> = stmt
-            .query_row([&index], |row| {
-                let node_serialized: Vec<u8> = row.get(0).unwrap();
-                Ok(Some(node_serialized))
-            })
-            .unwrap_or(None);
-
-        bincode::deserialize(&message_serialized.expect("[Error] Block not found")).unwrap()
+        // receipts outlive the pool, so that dropped messages can be reported
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_receipts (
+                id BLOB PRIMARY KEY,
+                payload_hash BLOB NOT NULL,
+                proposed_height INTEGER
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sender_nonces (
+                sender BLOB PRIMARY KEY,
+                nonce BLOB NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
     }
     fn get_all_messages(&self) -> Vec<Message> {
         let conn = Connection::open(&self.db_path).unwrap();
@@ -143,14 +458,75 @@ impl SqLiteMessagePool for MessagePool {
     }
     fn insert_message(&mut self, message: Message) {
         let conn = Connection::open(&self.db_path).unwrap();
+        let id = message.id();
         conn.execute(
-            "INSERT OR REPLACE INTO txns (tx) VALUES (?1)",
-            params![bincode::serialize(&message).unwrap()],
+            "INSERT OR REPLACE INTO txns (uid, tx) VALUES (?1, ?2)",
+            params![id, bincode::serialize(&message).unwrap()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO message_receipts (id, payload_hash, proposed_height) VALUES (?1, ?2, NULL)",
+            params![id, message.payload_hash()],
         )
         .unwrap();
         // todo: read size from db
         self.size += 1;
     }
+    fn contains_message(&self, id: &[u8]) -> bool {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.query_row("SELECT 1 FROM txns WHERE uid = ?1", params![id], |_| Ok(()))
+            .optional()
+            .unwrap()
+            .is_some()
+    }
+    fn get_receipt(&self, id: &[u8]) -> Option<PoolReceipt> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.query_row(
+            "SELECT payload_hash, proposed_height FROM message_receipts WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(PoolReceipt {
+                    payload_hash: row.get(0)?,
+                    proposed_height: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .unwrap()
+    }
+    fn mark_proposed(&mut self, ids: &[Vec<u8>], height: u32) {
+        let conn = Connection::open(&self.db_path).unwrap();
+        for id in ids {
+            conn.execute(
+                "UPDATE message_receipts SET proposed_height = ?1 WHERE id = ?2",
+                params![height, id],
+            )
+            .unwrap();
+        }
+    }
+    fn check_and_record_nonce(&mut self, sender: &[u8], nonce: u64) -> bool {
+        let conn = Connection::open(&self.db_path).unwrap();
+        // nonces are stored big endian, sqlite integers can't hold all u64 values
+        let last_nonce: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT nonce FROM sender_nonces WHERE sender = ?1",
+                params![sender],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        if last_nonce
+            .is_some_and(|last_nonce| nonce <= u64::from_be_bytes(last_nonce.try_into().unwrap()))
+        {
+            return false;
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO sender_nonces (sender, nonce) VALUES (?1, ?2)",
+            params![sender, nonce.to_be_bytes()],
+        )
+        .unwrap();
+        true
+    }
     fn reinitialize(&mut self) {
         // todo: remove when reading size from db
         self.size = 0;
@@ -160,67 +536,560 @@ impl SqLiteMessagePool for MessagePool {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureKind {
+    Proposal,
+    Commitment,
+}
+impl SignatureKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SignatureKind::Proposal => "proposal",
+            SignatureKind::Commitment => "commitment",
+        }
+    }
+}
+// slashing protection: remembers the header this validator signed at each height, so that
+// a restarted node or a node in a later round never signs two different blocks for a height
+pub trait SqLiteSigningHistory {
+    fn setup(&self);
+    // records the header hash and returns false if a different header was signed before
+    fn check_and_record(&mut self, height: u32, kind: SignatureKind, header_hash: &[u8]) -> bool;
+    // whether this validator signed any header at the height
+    fn signed_at(&self, height: u32) -> bool;
+}
+pub struct SigningHistory {
+    pub db_path: String,
+}
+impl SqLiteSigningHistory for SigningHistory {
+    fn setup(&self) {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS signed_headers (
+            height INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            header_hash BLOB NOT NULL,
+            PRIMARY KEY (height, kind)
+            )",
+            [],
+        )
+        .unwrap();
+    }
+    fn check_and_record(&mut self, height: u32, kind: SignatureKind, header_hash: &[u8]) -> bool {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO signed_headers (height, kind, header_hash) VALUES (?1, ?2, ?3)",
+            params![height, kind.as_str(), header_hash],
+        )
+        .unwrap();
+        let signed_hash: Vec<u8> = conn
+            .query_row(
+                "SELECT header_hash FROM signed_headers WHERE height = ?1 AND kind = ?2",
+                params![height, kind.as_str()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        signed_hash == header_hash
+    }
+    fn signed_at(&self, height: u32) -> bool {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM signed_headers WHERE height = ?1)",
+            [&height],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+}
+
+// a validator signed two different blocks for the same height, round and view, both payloads
+// are the signed header hashes so that anyone can check the signatures
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Equivocation {
+    pub validator: GenericPublicKey,
+    pub kind: SignatureKind,
+    pub height: u32,
+    pub round: u32,
+    pub view: u32,
+    pub first_payload: Vec<u8>,
+    pub first_signature: GenericSignature,
+    pub second_payload: Vec<u8>,
+    pub second_signature: GenericSignature,
+}
+// evidence of view changes and misbehaving validators, kept so that operators can
+// audit why a round changed its proposer and act on validators that equivocated
+pub trait SqLiteEvidenceStore {
+    fn setup(&self);
+    fn insert_timeout_certificate(&mut self, certificate: &TimeoutCertificate);
+    fn get_timeout_certificates(&self, height: u32) -> Vec<TimeoutCertificate>;
+    // remembers a verified signature and returns the evidence if the validator signed
+    // a different payload for the same height, round, view and kind before
+    #[allow(clippy::too_many_arguments)]
+    fn record_signature(
+        &mut self,
+        height: u32,
+        round: u32,
+        view: u32,
+        kind: SignatureKind,
+        validator: &[u8],
+        payload: &[u8],
+        signature: &[u8],
+    ) -> Option<Equivocation>;
+    fn get_equivocations(&self) -> Vec<Equivocation>;
+}
+pub struct EvidenceStore {
+    pub db_path: String,
+}
+impl SqLiteEvidenceStore for EvidenceStore {
+    fn setup(&self) {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS timeout_certificates (
+            height INTEGER NOT NULL,
+            round INTEGER NOT NULL,
+            view INTEGER NOT NULL,
+            certificate BLOB NOT NULL,
+            PRIMARY KEY (height, round, view)
+            )",
+            [],
+        )
+        .unwrap();
+        // signatures seen for heights that are not finalized yet
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS observed_signatures (
+            height INTEGER NOT NULL,
+            round INTEGER NOT NULL,
+            view INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            validator BLOB NOT NULL,
+            payload BLOB NOT NULL,
+            signature BLOB NOT NULL,
+            PRIMARY KEY (height, round, view, kind, validator)
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS equivocations (
+            height INTEGER NOT NULL,
+            round INTEGER NOT NULL,
+            view INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            validator BLOB NOT NULL,
+            evidence BLOB NOT NULL,
+            PRIMARY KEY (height, round, view, kind, validator)
+            )",
+            [],
+        )
+        .unwrap();
+    }
+    fn insert_timeout_certificate(&mut self, certificate: &TimeoutCertificate) {
+        let conn = Connection::open(&self.db_path).unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO timeout_certificates (height, round, view, certificate) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                certificate.height,
+                certificate.round,
+                certificate.view,
+                bincode::serialize(certificate).unwrap()
+            ],
+        )
+        .unwrap();
+    }
+    fn get_timeout_certificates(&self, height: u32) -> Vec<TimeoutCertificate> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT certificate FROM timeout_certificates WHERE height = ?1 ORDER BY round, view")
+            .unwrap();
+        let certificates = stmt
+            .query_map([&height], |row| row.get::<_, Vec<u8>>(0))
+            .unwrap();
+        certificates
+            .map(|certificate| bincode::deserialize(&certificate.unwrap()).unwrap())
+            .collect()
+    }
+    fn record_signature(
+        &mut self,
+        height: u32,
+        round: u32,
+        view: u32,
+        kind: SignatureKind,
+        validator: &[u8],
+        payload: &[u8],
+        signature: &[u8],
+    ) -> Option<Equivocation> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        // signatures for lower heights can no longer conflict with a proposal
+        conn.execute(
+            "DELETE FROM observed_signatures WHERE height < ?1",
+            params![height],
+        )
+        .unwrap();
+        let observed: Option<(Vec<u8>, Vec<u8>)> = conn
+            .query_row(
+                "SELECT payload, signature FROM observed_signatures WHERE height = ?1 AND round = ?2 AND view = ?3 AND kind = ?4 AND validator = ?5",
+                params![height, round, view, kind.as_str(), validator],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .unwrap();
+        let (first_payload, first_signature) = match observed {
+            Some(observed) => observed,
+            None => {
+                conn.execute(
+                    "INSERT INTO observed_signatures (height, round, view, kind, validator, payload, signature) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![height, round, view, kind.as_str(), validator, payload, signature],
+                )
+                .unwrap();
+                return None;
+            }
+        };
+        if first_payload == payload {
+            return None;
+        }
+        let equivocation = Equivocation {
+            validator: validator.to_vec(),
+            kind,
+            height,
+            round,
+            view,
+            first_payload,
+            first_signature,
+            second_payload: payload.to_vec(),
+            second_signature: signature.to_vec(),
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO equivocations (height, round, view, kind, validator, evidence) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                height,
+                round,
+                view,
+                kind.as_str(),
+                validator,
+                bincode::serialize(&equivocation).unwrap()
+            ],
+        )
+        .unwrap();
+        Some(equivocation)
+    }
+    fn get_equivocations(&self) -> Vec<Equivocation> {
+        let conn = Connection::open(&self.db_path).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT evidence FROM equivocations ORDER BY height, round, view")
+            .unwrap();
+        let equivocations = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0)).unwrap();
+        equivocations
+            .map(|equivocation| bincode::deserialize(&equivocation.unwrap()).unwrap())
+            .collect()
+    }
+}
+
 pub struct InMemoryConsensus {
-    pub validators: Vec<VerifyingKey>,
+    pub genesis: Genesis,
+    pub validators: ValidatorSet,
     pub local_validator: VerifyingKey,
-    pub local_signing_key: SigningKey,
+    pub signer: Arc<dyn Signer>,
+    pub signing_history: SigningHistory,
+    pub evidence: EvidenceStore,
+    pub leader_election: Box<dyn LeaderElection>,
     pub commitments: Vec<Vec<ConsensusCommitment>>,
     pub round_winner: Option<VerifyingKey>,
     pub proposed: bool,
     pub committed: bool,
-    pub signed: bool,
     pub lowest_block: Option<Vec<u8>>,
+    // the view within the current round, advanced by a quorum of timeout votes
+    pub view: u32,
+    // the certificate that advanced the round to the current view
+    pub view_certificate: Option<TimeoutCertificate>,
+    pub timeout_votes: Vec<TimeoutVote>,
+    pub timeout_voted: bool,
 }
 impl InMemoryConsensus {
-    #[allow(unused)]
-    pub fn empty() -> Self {
-        Self {
-            validators: Vec::new(),
-            local_validator: v1_vk_deserialized(),
-            local_signing_key: v2_sk_deserialized(),
-            commitments: Vec::new(),
-            round_winner: None,
-            proposed: false,
-            committed: false,
-            signed: false,
-            lowest_block: None,
+    pub fn from_genesis(
+        genesis: Genesis,
+        signer: Arc<dyn Signer>,
+        signing_history: SigningHistory,
+        evidence: EvidenceStore,
+    ) -> InMemoryConsensus {
+        if !genesis.validator_set().contains(&signer.public_key()) {
+            println!("[Warning] Local validator is not part of the genesis validator set");
         }
-    }
-    pub fn empty_with_default_validators() -> InMemoryConsensus {
-        use crate::config::consensus::v2_vk_deserialized;
-        let local_validator_test_id = env::var("LOCAL_VALIDATOR").unwrap_or(0.to_string());
-        let local_validator = if local_validator_test_id == "0" {
-            (v1_sk_deserialized(), v1_vk_deserialized())
-        } else if local_validator_test_id == "1" {
-            (v2_sk_deserialized(), v2_vk_deserialized())
-        } else if local_validator_test_id == "2" {
-            (v3_sk_deserialized(), v3_vk_deserialized())
-        } else {
-            (v4_sk_deserialized(), v4_vk_deserialized())
-        };
         Self {
-            validators: vec![
-                v1_vk_deserialized(),
-                v2_vk_deserialized(),
-                v3_vk_deserialized(),
-                v4_vk_deserialized(),
-            ],
-            local_validator: local_validator.1,
-            local_signing_key: local_validator.0,
+            validators: genesis.validator_set(),
+            leader_election: genesis.leader_election.leader_election(),
+            genesis,
+            local_validator: signer.public_key(),
+            signer,
+            signing_history,
+            evidence,
             commitments: Vec::new(),
             round_winner: None,
             proposed: false,
             committed: false,
-            signed: false,
             lowest_block: None,
+            view: 0,
+            view_certificate: None,
+            timeout_votes: Vec::new(),
+            timeout_voted: false,
         }
     }
+    pub fn total_voting_power(&self) -> u64 {
+        self.validators.total_stake()
+    }
+    pub fn required_voting_power(&self) -> u64 {
+        self.genesis
+            .quorum
+            .required_voting_power(self.total_voting_power())
+    }
     pub fn reinitialize(&mut self) {
         self.commitments = Vec::new();
         self.round_winner = None;
         self.proposed = false;
         self.committed = false;
-        self.signed = false;
         self.lowest_block = None;
+        self.view = 0;
+        self.view_certificate = None;
+        self.timeout_votes = Vec::new();
+        self.timeout_voted = false;
     }
+    // hands the round to the fallback proposer of the certified view, skipping the commitment
+    pub fn advance_view(&mut self, round: u32, certificate: TimeoutCertificate) {
+        self.view = certificate.view;
+        self.view_certificate = Some(certificate);
+        self.round_winner = Some(fallback_proposer(round, self.view, &self.validators));
+        self.committed = true;
+        self.proposed = false;
+        self.lowest_block = None;
+        self.timeout_votes = Vec::new();
+        self.timeout_voted = false;
+    }
+}
+
+#[test]
+fn test_signing_history_refuses_conflicting_payloads() {
+    let db = TempDb::new("signing-history");
+    let mut signing_history = SigningHistory {
+        db_path: db.path.clone(),
+    };
+    signing_history.setup();
+    assert!(!signing_history.signed_at(1));
+    assert!(signing_history.check_and_record(1, SignatureKind::Proposal, &[1]));
+    assert!(signing_history.signed_at(1));
+    assert!(signing_history.check_and_record(1, SignatureKind::Proposal, &[1]));
+    assert!(!signing_history.check_and_record(1, SignatureKind::Proposal, &[2]));
+    assert!(signing_history.check_and_record(1, SignatureKind::Commitment, &[2]));
+    assert!(!signing_history.check_and_record(1, SignatureKind::Commitment, &[1]));
+    // the history survives a restart
+    let mut restarted_history = SigningHistory {
+        db_path: db.path.clone(),
+    };
+    restarted_history.setup();
+    assert!(!restarted_history.check_and_record(1, SignatureKind::Commitment, &[1]));
+    assert!(restarted_history.check_and_record(1, SignatureKind::Commitment, &[2]));
+    assert!(restarted_history.check_and_record(2, SignatureKind::Commitment, &[1]));
+}
+
+#[test]
+fn test_evidence_store_detects_equivocation() {
+    let db = TempDb::new("evidence");
+    let mut evidence = EvidenceStore {
+        db_path: db.path.clone(),
+    };
+    evidence.setup();
+    let validator = vec![2; 33];
+    assert!(evidence
+        .record_signature(1, 1, 0, SignatureKind::Commitment, &validator, &[1], &[1])
+        .is_none());
+    assert!(evidence
+        .record_signature(1, 1, 0, SignatureKind::Commitment, &validator, &[1], &[1])
+        .is_none());
+    // proposals and commitments of a validator are tracked separately
+    assert!(evidence
+        .record_signature(1, 1, 0, SignatureKind::Proposal, &validator, &[2], &[2])
+        .is_none());
+    let equivocation = evidence
+        .record_signature(1, 1, 0, SignatureKind::Commitment, &validator, &[2], &[2])
+        .expect("Conflicting signature was not detected");
+    assert_eq!(equivocation.first_payload, vec![1]);
+    assert_eq!(equivocation.second_payload, vec![2]);
+    assert_eq!(evidence.get_equivocations().len(), 1);
+    // signing the Block of the next view after a view change is no equivocation
+    assert!(evidence
+        .record_signature(1, 1, 1, SignatureKind::Commitment, &validator, &[3], &[3])
+        .is_none());
+    assert_eq!(evidence.get_equivocations().len(), 1);
+}
+
+#[test]
+fn test_state_roots_by_height() {
+    let db = TempDb::new("state-roots");
+    let mut block_state = BlockStore {
+        db_path: db.path.clone(),
+    };
+    block_state.setup();
+    block_state.trigger_genesis(0, vec![]);
+    let root = Root {
+        hash: Some(vec![1; 32]),
+        left: None,
+        right: None,
+    };
+    block_state.insert_state_root(1, root.clone());
+    let root_hash = |height: u32| block_state.get_state_root(height).map(|root| root.hash);
+    assert_eq!(root_hash(0), Some(None));
+    assert_eq!(root_hash(1), Some(root.hash.clone()));
+    assert_eq!(root_hash(2), None);
+    assert_eq!(block_state.latest_state_root().unwrap().1.hash, root.hash);
+}
+
+#[test]
+fn test_sequence_numbers() {
+    let db = TempDb::new("sequence");
+    let mut block_state = BlockStore {
+        db_path: db.path.clone(),
+    };
+    block_state.setup();
+    block_state.trigger_genesis(0, vec![]);
+    let message = |data: u8| Message {
+        data: vec![data],
+        timestamp: 0,
+        envelope: None,
+    };
+    let block = |height: u32, messages: Vec<Message>| {
+        let mut block = Block::genesis(0);
+        block.header.height = height;
+        block.messages = messages;
+        block
+    };
+    let certificate = || QuorumCertificate {
+        block_hash: vec![],
+        validator_set_id: vec![],
+        signatures: vec![],
+    };
+    // equal payloads are sequenced twice
+    block_state.insert_block(1, block(1, vec![message(1), message(1)]), certificate());
+    block_state.insert_block(2, block(2, vec![message(2)]), certificate());
+    // storing the tip again keeps the numbering
+    block_state.insert_block(2, block(2, vec![message(2)]), certificate());
+    let sequenced_message = block_state.get_message_by_sequence(2).unwrap();
+    assert_eq!(
+        sequenced_message.location,
+        MessageLocation {
+            sequence: 2,
+            height: 2,
+            index: 0,
+        }
+    );
+    assert_eq!(sequenced_message.message.data, vec![2]);
+    assert!(block_state.get_message_by_sequence(3).is_none());
+    let locations = block_state.get_message_locations(&message(1).payload_hash());
+    assert_eq!(
+        locations
+            .iter()
+            .map(|location| (location.sequence, location.index))
+            .collect::<Vec<(u64, u32)>>(),
+        vec![(0, 0), (1, 1)]
+    );
+}
+
+#[test]
+fn test_finalized_nonces() {
+    use crate::crypto::ecdsa::Keypair;
+    let db = TempDb::new("finalized-nonces");
+    let mut block_state = BlockStore {
+        db_path: db.path.clone(),
+    };
+    block_state.setup();
+    block_state.trigger_genesis(0, vec![]);
+    let sender = Keypair::new();
+    let message = |nonce: u64| {
+        Message {
+            data: vec![1],
+            timestamp: 0,
+            envelope: None,
+        }
+        .sign(&sender, "diseq-local-net", nonce)
+    };
+    let block = |height: u32, messages: Vec<Message>| {
+        let mut block = Block::genesis(0);
+        block.header.height = height;
+        block.messages = messages;
+        block
+    };
+    let certificate = || QuorumCertificate {
+        block_hash: vec![],
+        validator_set_id: vec![],
+        signatures: vec![],
+    };
+    assert_eq!(
+        block_state.get_finalized_nonce(&sender.serialize_vk()),
+        None
+    );
+    block_state.insert_block(1, block(1, vec![message(1), message(300)]), certificate());
+    block_state.insert_block(2, block(2, vec![message(u64::MAX)]), certificate());
+    assert_eq!(
+        block_state.get_finalized_nonce(&sender.serialize_vk()),
+        Some(u64::MAX)
+    );
+    // storing a Block again replaces the nonces of that height
+    block_state.insert_block(2, block(2, vec![]), certificate());
+    assert_eq!(
+        block_state.get_finalized_nonce(&sender.serialize_vk()),
+        Some(300)
+    );
+    assert_eq!(
+        block_state.get_finalized_nonce(&Keypair::new().serialize_vk()),
+        None
+    );
+}
+
+#[test]
+fn test_message_pool_receipts() {
+    let db = TempDb::new("pool");
+    let mut pool_state = MessagePool {
+        size: 0,
+        db_path: db.path.clone(),
+    };
+    pool_state.setup();
+    let message = Message {
+        data: vec![1],
+        timestamp: 0,
+        envelope: None,
+    };
+    // equal messages share an id and are pooled once
+    pool_state.insert_message(message.clone());
+    pool_state.insert_message(message.clone());
+    assert_eq!(pool_state.get_all_messages().len(), 1);
+    assert!(pool_state.contains_message(&message.id()));
+    pool_state.mark_proposed(&[message.id()], 1);
+    pool_state.reinitialize();
+    assert!(!pool_state.contains_message(&message.id()));
+    assert_eq!(
+        pool_state.get_receipt(&message.id()),
+        Some(PoolReceipt {
+            payload_hash: message.payload_hash(),
+            proposed_height: Some(1),
+        })
+    );
+}
+
+#[test]
+fn test_sender_nonces() {
+    let db = TempDb::new("nonces");
+    let mut pool_state = MessagePool {
+        size: 0,
+        db_path: db.path.clone(),
+    };
+    pool_state.setup();
+    assert!(pool_state.check_and_record_nonce(&[1], 0));
+    assert!(!pool_state.check_and_record_nonce(&[1], 0));
+    // nonces may skip but never go back
+    assert!(pool_state.check_and_record_nonce(&[1], 5));
+    assert!(!pool_state.check_and_record_nonce(&[1], 4));
+    assert!(pool_state.check_and_record_nonce(&[2], 0));
+    assert!(pool_state.check_and_record_nonce(&[2], u64::MAX));
+    assert!(!pool_state.check_and_record_nonce(&[2], u64::MAX));
+    // nonces survive the pool being cleared
+    pool_state.reinitialize();
+    assert!(!pool_state.check_and_record_nonce(&[1], 5));
 }
@@ -0,0 +1,18 @@
+// a sqlite database in the temp directory, removed when the test ends even if it fails
+pub struct TempDb {
+    pub path: String,
+}
+impl TempDb {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{}.sqlite", name, std::process::id()));
+        Self {
+            path: path.to_string_lossy().to_string(),
+        }
+    }
+}
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        // the database is only created once something writes to it
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -1,45 +1,326 @@
+use crate::crypto::{ecdsa::Keypair, merkle::merkle_root, sparse_merkle::EMPTY_ROOT};
+use anyhow::{anyhow, Result};
+use ciphercurve_trie::store::types::{Hashable, Leaf, Root};
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
 use risc0_zkvm::Receipt;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 pub type GenericSignature = Vec<u8>;
 pub type Timestamp = u32;
 pub type GenericMessageData = Vec<u8>;
 pub type GenericPublicKey = Vec<u8>;
 
+// the signed part of a Block, it links to the parent and commits to the messages
+// and to the trie root after the messages were inserted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockHeader {
+    pub height: u32,
+    pub parent_hash: Vec<u8>,
+    pub messages_root: Vec<u8>,
+    pub state_root: Option<Vec<u8>>,
+    // root of the sparse merkle index of all payload hashes sequenced so far
+    pub message_index_root: Vec<u8>,
+    pub proposer: GenericPublicKey,
+    pub timestamp: Timestamp,
+    // the view of the round the Block was proposed in, 0 unless the round winner timed out
+    pub view: u32,
+}
+impl BlockHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+    pub fn hash(&self) -> Vec<u8> {
+        Sha256::digest(self.to_bytes()).to_vec()
+    }
+}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Block {
-    pub height: u32,
+    pub header: BlockHeader,
     pub messages: Vec<Message>,
+    // the proposer's signature over the header hash
     pub signature: Option<GenericSignature>,
     pub commitments: Option<Vec<BlockCommitment>>,
-    pub timestamp: Timestamp,
 }
 impl Block {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let temp_block: Block = Block {
-            height: self.height,
-            messages: self.messages.clone(),
-            signature: None,
+    // the genesis Block has no parent and no proposer, the trie starts out empty
+    pub fn genesis(timestamp: Timestamp) -> Self {
+        Block {
+            header: BlockHeader {
+                height: 0,
+                parent_hash: vec![],
+                messages_root: Block::messages_root(&[]),
+                state_root: Root::empty().hash,
+                message_index_root: EMPTY_ROOT.to_vec(),
+                proposer: vec![],
+                timestamp,
+                view: 0,
+            },
+            signature: Some(vec![]),
+            messages: vec![],
             commitments: None,
-            timestamp: self.timestamp,
-        };
-        bincode::serialize(&temp_block).unwrap()
+        }
+    }
+    pub fn hash(&self) -> Vec<u8> {
+        self.header.hash()
+    }
+    pub fn messages_root(messages: &[Message]) -> Vec<u8> {
+        merkle_root(
+            &messages
+                .iter()
+                .map(|message| bincode::serialize(message).unwrap())
+                .collect::<Vec<Vec<u8>>>(),
+        )
+    }
+}
+// finality evidence of a stored Block, signatures over the Block by validators
+// whose combined voting power meets the quorum of the validator set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuorumCertificate {
+    pub block_hash: Vec<u8>,
+    pub validator_set_id: Vec<u8>,
+    pub signatures: Vec<BlockCommitment>,
+}
+// a Block as served to peers, together with the certificate that finalized it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FinalizedBlock {
+    pub block: Block,
+    pub quorum_certificate: QuorumCertificate,
+}
+impl FinalizedBlock {
+    pub fn finalized_header(&self) -> FinalizedHeader {
+        FinalizedHeader {
+            header: self.block.header.clone(),
+            quorum_certificate: self.quorum_certificate.clone(),
+        }
     }
 }
+// the part of a FinalizedBlock a light client follows, without the messages
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FinalizedHeader {
+    pub header: BlockHeader,
+    pub quorum_certificate: QuorumCertificate,
+}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
     pub data: GenericMessageData,
     pub timestamp: Timestamp,
+    // unsigned messages are anonymous
+    #[serde(default)]
+    pub envelope: Option<MessageEnvelope>,
+}
+// the identity of the sender of a message, the signature covers the chain id, the data,
+// the timestamp and the nonce. Nonces of a sender must increase from message to message
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageEnvelope {
+    pub sender: GenericPublicKey,
+    pub nonce: u64,
+    pub signature: GenericSignature,
+}
+impl Message {
+    pub fn signing_payload(&self, chain_id: &str, nonce: u64) -> Vec<u8> {
+        bincode::serialize(&(chain_id, &self.data, self.timestamp, nonce)).unwrap()
+    }
+    // attaches an envelope signed by the sender
+    #[allow(unused)]
+    pub fn sign(mut self, sender: &Keypair, chain_id: &str, nonce: u64) -> Self {
+        self.envelope = Some(MessageEnvelope {
+            sender: sender.serialize_vk(),
+            nonce,
+            signature: sender
+                .sign_data(&self.signing_payload(chain_id, nonce))
+                .to_bytes()
+                .to_vec(),
+        });
+        self
+    }
+    // unsigned messages are valid
+    pub fn verify_envelope(&self, chain_id: &str) -> Result<()> {
+        let Some(envelope) = &self.envelope else {
+            return Ok(());
+        };
+        let sender = VerifyingKey::from_sec1_bytes(&envelope.sender)
+            .map_err(|_| anyhow!("Invalid sender key"))?;
+        let signature = Signature::from_slice(&envelope.signature)
+            .map_err(|_| anyhow!("Malformed message signature"))?;
+        sender
+            .verify(&self.signing_payload(chain_id, envelope.nonce), &signature)
+            .map_err(|_| anyhow!("Invalid message signature"))
+    }
+    // stable handle returned to the submitter, equal messages share an id
+    pub fn id(&self) -> Vec<u8> {
+        Sha256::digest(bincode::serialize(self).unwrap()).to_vec()
+    }
+    // the key of the message in the message index
+    pub fn payload_hash(&self) -> Vec<u8> {
+        Sha256::digest(&self.data).to_vec()
+    }
+    // messages are keyed by their position in the trie, the bits of the big endian
+    // height followed by the index in the Block, so equal payloads never collide
+    pub fn trie_key(height: u32, index: u32) -> Vec<u8> {
+        [height.to_be_bytes(), index.to_be_bytes()]
+            .concat()
+            .iter()
+            .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+            .collect()
+    }
+    // the trie leaf of the message at its position, the value is the payload hash
+    pub fn trie_leaf(&self, height: u32, index: u32) -> Leaf {
+        let mut leaf = Leaf::new(Message::trie_key(height, index), Some(self.payload_hash()));
+        leaf.hash();
+        leaf
+    }
+}
+// where a finalized message sits in the total order, the sequence number counts all
+// messages of all Blocks before it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageLocation {
+    pub sequence: u64,
+    pub height: u32,
+    pub index: u32,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SequencedMessage {
+    pub location: MessageLocation,
+    pub message: Message,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InclusionStatus {
+    // stored in a finalized Block
+    Included,
+    // in the message pool of the node that was asked
+    Pending,
+    Unknown,
+}
+// the answer to a lookup by payload hash, a payload that was sequenced more than once
+// is reported at its first location
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageLookup {
+    pub status: InclusionStatus,
+    pub message: Option<Message>,
+    pub location: Option<MessageLocation>,
+}
+// returned by /schedule, the id is used to query the status of the message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageReceipt {
+    pub id: String,
+    pub payload_hash: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MessageStatus {
+    Pending {
+        reason: String,
+    },
+    Included {
+        location: MessageLocation,
+        reason: String,
+    },
+    Dropped {
+        reason: String,
+    },
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BlockCommitment {
-    // a signature over the serialized
-    // messages in the Block
+    // a signature over the header hash
+    // of the Block
     pub signature: GenericSignature,
     pub validator: GenericPublicKey,
     pub timestamp: Timestamp,
 }
+// proves the random number of a commitment, the variant depends on
+// the leader election backend chosen in the genesis file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ElectionProof {
+    RoundRobin,
+    Ecvrf(Vec<u8>),
+    Zk(Receipt),
+}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ConsensusCommitment {
     pub validator: GenericPublicKey,
-    pub receipt: Receipt,
+    // the commitment is only valid for this chain, height and round,
+    // all three are bound into the seed of the random number
+    pub chain_id: String,
+    pub height: u32,
+    pub round: u32,
+    pub proof: ElectionProof,
+    // a signature by the validator over the commitment
+    pub signature: GenericSignature,
+}
+impl ConsensusCommitment {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let temp_commitment: ConsensusCommitment = ConsensusCommitment {
+            validator: self.validator.clone(),
+            chain_id: self.chain_id.clone(),
+            height: self.height,
+            round: self.round,
+            proof: self.proof.clone(),
+            signature: vec![],
+        };
+        bincode::serialize(&temp_commitment).unwrap()
+    }
+}
+// a validator's vote to abandon the current view of a round, sent when no block
+// was finalized within the view timeout
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeoutVote {
+    pub validator: GenericPublicKey,
+    pub chain_id: String,
+    pub height: u32,
+    pub round: u32,
+    // the view the validator wants to advance to
+    pub view: u32,
+    pub signature: GenericSignature,
+    // the certificate of the view before, lets a node that missed that view change catch
+    // up. It is not signed, the certificate verifies on its own
+    #[serde(default)]
+    pub certificate: Option<TimeoutCertificate>,
+}
+impl TimeoutVote {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let temp_vote: TimeoutVote = TimeoutVote {
+            validator: self.validator.clone(),
+            chain_id: self.chain_id.clone(),
+            height: self.height,
+            round: self.round,
+            view: self.view,
+            signature: vec![],
+            certificate: None,
+        };
+        bincode::serialize(&temp_vote).unwrap()
+    }
+}
+// evidence of a view change, the votes carry the quorum that advanced to the view
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeoutCertificate {
+    pub height: u32,
+    pub round: u32,
+    pub view: u32,
+    pub votes: Vec<TimeoutVote>,
+}
+
+#[test]
+fn test_signed_message_envelope() {
+    let chain_id = "diseq-local-net";
+    let sender = Keypair::new();
+    let message = Message {
+        data: vec![1, 2, 3],
+        timestamp: 0,
+        envelope: None,
+    };
+    assert!(message.verify_envelope(chain_id).is_ok());
+    let signed_message = message.sign(&sender, chain_id, 1);
+    assert!(signed_message.verify_envelope(chain_id).is_ok());
+    // the signature covers the chain, the data and the nonce
+    assert!(signed_message.verify_envelope("diseq-other-net").is_err());
+    let mut tampered_message = signed_message.clone();
+    tampered_message.data = vec![1, 2, 4];
+    assert!(tampered_message.verify_envelope(chain_id).is_err());
+    let mut replayed_message = signed_message.clone();
+    replayed_message.envelope.as_mut().unwrap().nonce = 2;
+    assert!(replayed_message.verify_envelope(chain_id).is_err());
+    let mut impersonated_message = signed_message;
+    impersonated_message.envelope.as_mut().unwrap().sender = Keypair::new().serialize_vk();
+    assert!(impersonated_message.verify_envelope(chain_id).is_err());
 }
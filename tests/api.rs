@@ -1,18 +1,12 @@
 #[cfg(test)]
 mod tests {
-    use ciphercurve_trie::{
-        merkle::{verify_merkle_proof, MerkleProof},
-        store::types::{Hashable, Leaf, Root},
-    };
-    use l2_sequencer::types::Message;
-    use prover::generate_random_number;
+    use ciphercurve_trie::merkle::{verify_merkle_proof, MerkleProof};
+    use l2_sequencer::crypto::ecdsa::Keypair;
+    use l2_sequencer::types::{ConsensusCommitment, ElectionProof};
+    use l2_sequencer::types::{FinalizedBlock, Message, MessageReceipt, MessageStatus};
     use reqwest::{Client, Response};
-    use std::{env, time::Duration};
+    use std::time::Duration;
     use tokio::time::sleep;
-    use {
-        l2_sequencer::config::network::PEERS, l2_sequencer::gossipper::Gossipper,
-        l2_sequencer::types::ConsensusCommitment,
-    };
 
     async fn submit_message(client: Client, message_json: String) -> Response {
         client
@@ -24,9 +18,13 @@ mod tests {
             .unwrap()
     }
 
-    async fn request_merkle_proof(client: Client, message_key_json: String) -> Response {
+    async fn request_merkle_proof(
+        client: Client,
+        height: u32,
+        message_key_json: String,
+    ) -> Response {
         client
-            .post("http://127.0.0.1:8080/merkle_proof")
+            .post(format!("http://127.0.0.1:8080/merkle_proof/{}", height))
             .header("Content-Type", "application/json")
             .body(message_key_json)
             .send()
@@ -34,12 +32,51 @@ mod tests {
             .unwrap()
     }
 
-    async fn get_state_root_hash(client: Client) -> Response {
-        client
-            .get("http://127.0.0.1:8080/get/state_root_hash")
+    async fn get_height(client: Client) -> u32 {
+        let response = client
+            .get("http://127.0.0.1:8080/get/height")
             .send()
             .await
-            .unwrap()
+            .unwrap();
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    async fn get_message_status(client: Client, id: &str) -> MessageStatus {
+        let response = client
+            .get(format!("http://127.0.0.1:8080/message/{}/status", id))
+            .send()
+            .await
+            .unwrap();
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    // the node answers while it holds its locks, retry until the commitment was checked
+    async fn submit_commitment(client: Client, commitment: &ConsensusCommitment) -> String {
+        loop {
+            let response = client
+                .post("http://127.0.0.1:8080/commit")
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_string(commitment).unwrap())
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            if response != "[Error] Failed to obtain locks" {
+                return response;
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    async fn get_block(client: Client, height: u32) -> FinalizedBlock {
+        let response = client
+            .get(format!("http://127.0.0.1:8080/get/block/{}", height))
+            .send()
+            .await
+            .unwrap();
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
     }
 
     #[tokio::test]
@@ -48,52 +85,49 @@ mod tests {
         let message: Message = Message {
             data: vec![1, 2, 3, 4, 5],
             timestamp: 0,
+            envelope: None,
         };
         let message_json: String = serde_json::to_string(&message).unwrap();
         let message_response = submit_message(client.clone(), message_json).await;
-        assert_eq!(
-            message_response.text().await.unwrap(),
-            "[Ok] Message is being sequenced: Message { data: [1, 2, 3, 4, 5], timestamp: 0 }"
-        );
-        let mut node_trie_root: Option<Root> = None;
+        let receipt: MessageReceipt =
+            serde_json::from_str(&message_response.text().await.unwrap()).unwrap();
+        assert_eq!(receipt.id, hex::encode(message.id()));
+        // the Block and index the message was sequenced at
+        let mut position: Option<(FinalizedBlock, u32)> = None;
+        let mut next_height = get_height(client.clone()).await;
         // wait a maximum of ~ 10 blocks
         for _ in 0..10 {
-            let trie_root_json = get_state_root_hash(client.clone())
-                .await
-                .text()
-                .await
-                .unwrap();
-            let trie_root: Root = serde_json::from_str(&trie_root_json).unwrap();
-            match trie_root.hash.clone() {
-                Some(_) => {
-                    node_trie_root = Some(trie_root.clone());
-                    break;
-                }
-                None => {}
-            };
-            println!("No Trie Root found, waiting for next block...");
+            let tip = get_height(client.clone()).await;
+            while next_height < tip && position.is_none() {
+                let finalized_block = get_block(client.clone(), next_height).await;
+                position = finalized_block
+                    .block
+                    .messages
+                    .iter()
+                    .position(|sequenced| sequenced.data == message.data)
+                    .map(|index| (finalized_block, index as u32));
+                next_height += 1;
+            }
+            if position.is_some() {
+                break;
+            }
+            println!("Message not sequenced yet, waiting for next block...");
             sleep(Duration::from_secs(190)).await;
         }
-        let mut leaf = Leaf::new(Vec::new(), Some(message.data.clone()));
-        leaf.hash();
-        leaf.key = leaf
-            .hash
-            .clone()
-            .unwrap()
-            .iter()
-            .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1))
-            .collect();
-        leaf.hash();
-        let message_key_json = serde_json::to_string(&leaf.key).unwrap();
-        let merkle_proof_response = request_merkle_proof(client.clone(), message_key_json).await;
+        let (finalized_block, index) = position.expect("[Error] Message was not sequenced!");
+        let height = finalized_block.block.header.height;
+        let message_key_json = serde_json::to_string(&Message::trie_key(height, index)).unwrap();
+        let merkle_proof_response =
+            request_merkle_proof(client.clone(), height, message_key_json).await;
         let merkle_proof_json = merkle_proof_response.text().await.unwrap();
         let merkle_proof: MerkleProof = serde_json::from_str(&merkle_proof_json).unwrap();
         verify_merkle_proof(
             merkle_proof.nodes,
-            node_trie_root
-                .expect("[Error] No Trie Root present!")
-                .hash
-                .unwrap(),
+            finalized_block
+                .block
+                .header
+                .state_root
+                .expect("[Error] No Trie Root present!"),
         )
         .expect("Failed to verify Merkle proof!");
     }
@@ -104,30 +138,58 @@ mod tests {
         let message: Message = Message {
             data: vec![1, 2, 3, 4, 6],
             timestamp: 0,
+            envelope: None,
         };
         let message_json: String = serde_json::to_string(&message).unwrap();
         // note that currently a message may only be safely submitted to a single node
-        let message_response = submit_message(client, message_json).await;
+        let message_response = submit_message(client.clone(), message_json).await;
+        let receipt: MessageReceipt =
+            serde_json::from_str(&message_response.text().await.unwrap()).unwrap();
         assert_eq!(
-            message_response.text().await.unwrap(),
-            "[Ok] Message is being sequenced: Message { data: [1, 2, 3, 4, 5], timestamp: 0 }"
+            receipt,
+            MessageReceipt {
+                id: hex::encode(message.id()),
+                payload_hash: hex::encode(message.payload_hash()),
+            }
         );
+        let status = get_message_status(client, &receipt.id).await;
+        assert!(!matches!(status, MessageStatus::Dropped { .. }));
     }
 
     #[tokio::test]
     async fn test_commit() {
-        let receipt = generate_random_number(vec![0; 32], vec![0; 32]);
-        let consensus_commitment: ConsensusCommitment = ConsensusCommitment {
+        let client = Client::new();
+        // an unsigned commitment with an invalid validator key
+        let forged_commitment = ConsensusCommitment {
             validator: vec![0; 32],
-            receipt,
+            chain_id: "diseq-local-net".to_string(),
+            height: 1,
+            round: 1,
+            proof: ElectionProof::RoundRobin,
+            signature: vec![],
         };
-        let gossipper = Gossipper {
-            peers: PEERS.to_vec(),
-            client: Client::new(),
+        let response = submit_commitment(client.clone(), &forged_commitment).await;
+        assert!(
+            response.starts_with("[Error] Commitment rejected"),
+            "{}",
+            response
+        );
+        // a signed commitment of a key outside the validator set, for a height long past
+        let keypair = Keypair::new();
+        let mut stale_commitment = ConsensusCommitment {
+            validator: keypair.serialize_vk(),
+            height: 0,
+            ..forged_commitment
         };
-        env::set_var("API_HOST_WITH_PORT", "127.0.0.1:8081");
-        gossipper
-            .gossip_consensus_commitment(consensus_commitment)
-            .await;
+        stale_commitment.signature = keypair
+            .sign_data(&stale_commitment.to_bytes())
+            .to_bytes()
+            .to_vec();
+        let response = submit_commitment(client, &stale_commitment).await;
+        assert!(
+            response.starts_with("[Error] Commitment rejected"),
+            "{}",
+            response
+        );
     }
 }